@@ -0,0 +1,267 @@
+use bevy::math::Vec2;
+use super::board_state::Position;
+
+// Annotations are saved the way Lichess and ChessBase write them into PGN comments: [%csl ...] lists the circled squares
+// and [%cal ...] the arrows, each with its colour's letter in front (G, R, B or Y). For example,
+//
+//     [%csl Gd4,Re5][%cal Ge2e4,Bg1f3]
+//
+// circles d4 in green and e5 in red, and draws a green arrow from e2 to e4 and a blue one from g1 to f3.
+// The same goes into FEN2 comments, and into position.annotations under the FEN2 of the position it was drawn on.
+
+// The colours markers and arrows come in, picked by holding a modifier key while right-clicking:
+// none for green, Shift for red, Alt for blue and Ctrl for yellow - the same as on Lichess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Brush {
+    #[default]
+    Green,
+    Red,
+    Blue,
+    Yellow,
+}
+
+// How circles and arrows are drawn, in squares: both on screen and in screenshots.
+pub const CIRCLE_RADIUS: f32 = 0.44;
+pub const CIRCLE_WIDTH: f32 = 0.07;
+pub const ARROW_WIDTH: f32 = 0.18;
+
+// A square circled with a right-click.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkedSquare {
+    pub square: Position,
+    pub brush: Brush,
+}
+
+// An arrow drawn by dragging with the right mouse button, from one square to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arrow {
+    pub from: Position,
+    pub to: Position,
+    pub brush: Brush,
+}
+
+impl Arrow {
+    // Arrows along a knight's jump are drawn bent into an L, rather than cutting across the squares in between.
+    pub fn is_knight_jump(&self) -> bool {
+        let (dx, dy) = ((self.to.x - self.from.x).abs(), (self.to.y - self.from.y).abs());
+        (dx, dy) == (1, 2) || (dx, dy) == (2, 1)
+    }
+
+    /// The arrow's shape, given where the middles of its two squares are drawn and how big a square is:
+    /// the points its line runs through, and the three corners of its head.
+    /// The head ends a little short of the middle of the square, and the line stops where the head starts.
+    /// Knight jumps go along the long side of the L first, then turn.
+    pub fn outline(&self, from: Vec2, to: Vec2, tile_size: f32) -> (Vec<Vec2>, [Vec2; 3]) {
+        let mut points = vec![from];
+        if self.is_knight_jump() {
+            let corner = if (self.to.x - self.from.x).abs() == 2 {
+                Vec2::new(to.x, from.y)
+            } else {
+                Vec2::new(from.x, to.y)
+            };
+            points.push(corner);
+        }
+        let direction = (to - *points.last().unwrap()).normalize();
+        let tip = to - direction * tile_size * 0.1;
+        let base = tip - direction * tile_size * 0.4;
+        let across = Vec2::new(-direction.y, direction.x) * tile_size * 0.25;
+        points.push(base);
+        (points, [tip, base + across, base - across])
+    }
+}
+
+fn brush_letter(brush: Brush) -> char {
+    match brush {
+        Brush::Green => 'G',
+        Brush::Red => 'R',
+        Brush::Blue => 'B',
+        Brush::Yellow => 'Y',
+    }
+}
+
+fn parse_brush(c: char) -> Option<Brush> {
+    match c {
+        'G' => Some(Brush::Green),
+        'R' => Some(Brush::Red),
+        'B' => Some(Brush::Blue),
+        'Y' => Some(Brush::Yellow),
+        _ => None,
+    }
+}
+
+// "Gd4" - a colour and a square.
+fn parse_marker(entry: &str) -> Option<MarkedSquare> {
+    let brush = parse_brush(entry.chars().next()?)?;
+    let square = Position::from_algebraic(entry.get(1..)?)?;
+    Some(MarkedSquare { square, brush })
+}
+
+// "Ge2e4" - a colour and two squares.
+fn parse_arrow(entry: &str) -> Option<Arrow> {
+    let brush = parse_brush(entry.chars().next()?)?;
+    let from = Position::from_algebraic(entry.get(1..3)?)?;
+    let to = Position::from_algebraic(entry.get(3..)?)?;
+    Some(Arrow { from, to, brush })
+}
+
+/// What's been drawn over the board to point things out, as opposed to the pieces on it.
+/// Like the BoardState, this is the source of truth - the circles and arrows are redrawn from it whenever it changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotations {
+    pub markers: Vec<MarkedSquare>,
+    pub arrows: Vec<Arrow>,
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty() && self.arrows.is_empty()
+    }
+
+    /// Circles the square, or rubs the circle out if it's already there in the same colour.
+    /// A circle in another colour is painted over instead.
+    pub fn toggle_marker(&mut self, marker: MarkedSquare) {
+        match self.markers.iter().position(|drawn| drawn.square == marker.square) {
+            Some(i) if self.markers[i] == marker => {
+                self.markers.remove(i);
+            }
+            Some(i) => self.markers[i] = marker,
+            None => self.markers.push(marker),
+        }
+    }
+
+    /// Draws the arrow, or rubs it out if it's already there - same as for markers.
+    pub fn toggle_arrow(&mut self, arrow: Arrow) {
+        match self.arrows.iter().position(|drawn| (drawn.from, drawn.to) == (arrow.from, arrow.to)) {
+            Some(i) if self.arrows[i] == arrow => {
+                self.arrows.remove(i);
+            }
+            Some(i) => self.arrows[i] = arrow,
+            None => self.arrows.push(arrow),
+        }
+    }
+
+    /// Writes the annotations as [%csl ...][%cal ...] commands, leaving out whichever list is empty.
+    pub fn to_commands(&self) -> String {
+        let mut commands = String::new();
+        if !self.markers.is_empty() {
+            let markers: Vec<String> = self.markers.iter()
+                .map(|marker| format!("{}{}", brush_letter(marker.brush), marker.square.to_algebraic()))
+                .collect();
+            commands.push_str(&format!("[%csl {}]", markers.join(",")));
+        }
+        if !self.arrows.is_empty() {
+            let arrows: Vec<String> = self.arrows.iter()
+                .map(|arrow| format!("{}{}{}", brush_letter(arrow.brush), arrow.from.to_algebraic(), arrow.to.to_algebraic()))
+                .collect();
+            commands.push_str(&format!("[%cal {}]", arrows.join(",")));
+        }
+        commands
+    }
+
+    /// Picks the [%csl ...] and [%cal ...] commands out of a comment. Everything else in it - text, other commands,
+    /// entries that don't make sense - is skipped over, since comments can come from anywhere.
+    pub fn from_comment(comment: &str) -> Self {
+        let mut annotations = Annotations::default();
+        let mut rest = comment;
+        while let Some(start) = rest.find("[%") {
+            let (command, after) = rest[start + 2..].split_once(']').unwrap_or((&rest[start + 2..], ""));
+            rest = after;
+            let (name, entries) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+            let entries = entries.split(',').map(str::trim);
+            match name {
+                "csl" => entries.filter_map(parse_marker).for_each(|marker| annotations.toggle_marker(marker)),
+                "cal" => entries.filter_map(parse_arrow).for_each(|arrow| annotations.toggle_arrow(arrow)),
+                _ => {}
+            }
+        }
+        annotations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> Position {
+        Position::from_algebraic(name).unwrap()
+    }
+
+    fn arrow(from: &str, to: &str, brush: Brush) -> Arrow {
+        Arrow { from: square(from), to: square(to), brush }
+    }
+
+    #[test]
+    fn arrows_toggle() {
+        let mut annotations = Annotations::default();
+        annotations.toggle_arrow(arrow("e2", "e4", Brush::Green));
+        annotations.toggle_arrow(arrow("g1", "f3", Brush::Green));
+        annotations.toggle_arrow(arrow("e2", "e4", Brush::Green));
+        assert_eq!(annotations.arrows, [arrow("g1", "f3", Brush::Green)]);
+        // The other way round is a different arrow.
+        annotations.toggle_arrow(arrow("f3", "g1", Brush::Green));
+        assert_eq!(annotations.arrows.len(), 2);
+        // Another colour paints over it.
+        annotations.toggle_arrow(arrow("g1", "f3", Brush::Red));
+        assert_eq!(annotations.arrows[0], arrow("g1", "f3", Brush::Red));
+        assert_eq!(annotations.arrows.len(), 2);
+    }
+
+    #[test]
+    fn markers_toggle_instead_of_stacking() {
+        let mut annotations = Annotations::default();
+        let marker = |name, brush| MarkedSquare { square: square(name), brush };
+        annotations.toggle_marker(marker("d4", Brush::Green));
+        annotations.toggle_marker(marker("d4", Brush::Blue));
+        assert_eq!(annotations.markers, [marker("d4", Brush::Blue)]);
+        annotations.toggle_marker(marker("d4", Brush::Blue));
+        assert!(annotations.is_empty());
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let mut annotations = Annotations::default();
+        annotations.toggle_marker(MarkedSquare { square: square("d4"), brush: Brush::Green });
+        annotations.toggle_marker(MarkedSquare { square: square("e5"), brush: Brush::Red });
+        annotations.toggle_arrow(arrow("e2", "e4", Brush::Green));
+        annotations.toggle_arrow(arrow("g1", "f3", Brush::Blue));
+        let commands = annotations.to_commands();
+        assert_eq!(commands, "[%csl Gd4,Re5][%cal Ge2e4,Bg1f3]");
+        assert_eq!(Annotations::from_comment(&commands), annotations);
+        assert_eq!(Annotations::default().to_commands(), "");
+    }
+
+    #[test]
+    fn saved_in_fen2_comments() {
+        use crate::fen::{fen2_annotations, from_fen2, to_annotated_fen2};
+        use crate::rules::{apply_move, legal_moves};
+        let mut board = from_fen2("4k3/8/8/8/4K3/8/8/8 w").unwrap();
+        let e5 = Position::from_algebraic("e5").unwrap();
+        let mv = legal_moves(&board, Position::from_algebraic("e4").unwrap()).into_iter().find(|mv| mv.to == e5).unwrap();
+        apply_move(&mut board, &mv);
+        let annotations = Annotations::from_comment("[%csl Re5]");
+        let fen = to_annotated_fen2(&board, &annotations);
+        assert!(fen.ends_with(" {[%csl Re5] White wins by midline invasion}"));
+        assert_eq!(fen2_annotations(&fen), annotations);
+        // Only the position comes back - loading it doesn't count as the king crossing.
+        let mut loaded = from_fen2(&fen).unwrap();
+        assert!(!loaded.king_crossed);
+        loaded.king_crossed = true;
+        assert_eq!(loaded, board);
+    }
+
+    #[test]
+    fn other_things_in_comments_are_skipped() {
+        let annotations = Annotations::from_comment("A strong move. [%clk 0:01:00] [%cal Yb1c3, Xa1a2, Ga9a1][%csl Bh8 ]");
+        assert_eq!(annotations.arrows, [arrow("b1", "c3", Brush::Yellow)]);
+        assert_eq!(annotations.markers, [MarkedSquare { square: square("h8"), brush: Brush::Blue }]);
+        assert!(Annotations::from_comment("no commands here").is_empty());
+    }
+
+    #[test]
+    fn knight_jumps_are_bent() {
+        assert!(arrow("g1", "f3", Brush::Green).is_knight_jump());
+        assert!(arrow("b8", "d7", Brush::Green).is_knight_jump());
+        assert!(!arrow("e2", "e4", Brush::Green).is_knight_jump());
+        assert!(!arrow("a1", "c3", Brush::Green).is_knight_jump());
+    }
+}
@@ -1,8 +1,21 @@
 use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
 use iyes_loopless::prelude::*;
 use bevy::window::{CursorIcon, CursorMoved};
-use bevy_inspector_egui::{Inspectable, RegisterInspectable, WorldInspectorPlugin};
-use super::screenshot::take_screenshot;
+use bevy_inspector_egui::{RegisterInspectable, WorldInspectorPlugin};
+use super::screenshot::ScreenshotPlugin;
+use super::board_state::*;
+use super::fen::{export_fen2, import_fen2, from_fen2, STARTING_FEN2};
+use super::rules::{drop_moves, legal_destinations, legal_moves, winner, MoveKind, Victory};
+use super::duel::{draw_stone_counters, duel_input, update_stone_counters, Duel};
+use super::history::{
+    history_panel, play_move_from_board, pointer_on_board, promotion_prompt, step_through_history, GameHistory, PendingPromotion, PointerOverPanel,
+};
+use super::pgn::{export_pgn2, import_pgn2, Players};
+use super::annotations::{Annotations, Arrow, Brush, MarkedSquare, ARROW_WIDTH, CIRCLE_RADIUS, CIRCLE_WIDTH};
+use super::undo::{track_edits, undo_redo, UndoStack};
+use super::cli::StartupArgs;
+use super::fischer::{fischer_arg, fischer_random_board, setup_fischer_random};
 
 // A square on the board.
 #[derive(Component)]
@@ -22,18 +35,22 @@ enum FilesRanks {
     Rank,
 }
 
-// A co-ordinate of an entity on the board.
-// Not the same as algebraic notation, which itself is not necessary for the board editor
-#[derive(Component, Clone, Copy, PartialEq, Eq, Default, Inspectable, Debug)]
-struct Position {
-    x: i32,
-    y: i32,
-}
-
-// A marked square on the board.
+// The circle around a marked square.
 #[derive(Component)]
 struct Marker;
 
+// A dot or ring on a square the piece being dragged can legally move to.
+#[derive(Component)]
+struct MoveHint;
+
+// The line or head of an arrow drawn over the board.
+#[derive(Component)]
+struct ArrowShape;
+
+// The banner across the midline announcing who won - both its backing and its text.
+#[derive(Component)]
+struct Banner;
+
 // Component signifying a piece on the board and its position.
 #[derive(Component)]
 struct Piece {
@@ -85,56 +102,61 @@ struct CursorPos {
     sprite: Option<(Entity, Vec3)>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum PieceCursor {
-    King,
-    Queen,
-    Rook,
-    Bishop,
-    Knight,
-    Pawn,
+// The move list gets a panel down the right-hand side of the window, next to the board.
+pub const PANEL_WIDTH: f32 = 220.;
+
+// The board is a square as tall as the window, filling it apart from the move list's panel.
+// The camera is moved over to keep the middle of the board at (0, 0), so everything on it is placed as if it had the window to itself.
+pub fn board_size(window: &Window) -> f32 {
+    window.height()
+}
+
+/// Which way up the board is drawn: flipped puts Black at the bottom. F turns it around.
+#[derive(Default)]
+pub struct Flipped(pub bool);
+
+// Where a square of the board is drawn, counted in squares from the bottom left of the window.
+// Turning the board around twice gets you back where you started, so this also gives the square under a spot on the screen.
+pub fn screen_square(pos: Position, flipped: &Flipped) -> Position {
+    if flipped.0 {
+        Position::new(7 - pos.x, 7 - pos.y)
+    } else {
+        pos
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum CursorState {
     DragDrop,
+    // Playing a game: only the side to move can move, and only by the rules.
+    Play,
     Trash,
     Place(PieceCursor),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum ArmyStates {
-    Classic,
-    Nemesis,
-    Empowered,
-    Reaper,
-    TwoKings,
-    Animals
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum PieceColor {
-    White,
-    Black,
-}
-
 
 // Square colours
-const DARK: Color = Color::rgb(0.71, 0.533, 0.388);
-const LIGHT: Color = Color::rgb(0.941, 0.851, 0.71);
+pub const DARK: Color = Color::rgb(0.71, 0.533, 0.388);
+pub const LIGHT: Color = Color::rgb(0.941, 0.851, 0.71);
 const HIGHLIGHT: Color = Color::rgba(0.39, 0.54, 0.42, 0.75);
-const MARKER: Color = Color::rgba(0.39, 0.89, 0.957, 0.75);
-const MIDLINE: Color = Color::rgb(0.06, 0.06, 0.74);
+pub const MIDLINE: Color = Color::rgb(0.06, 0.06, 0.74);
+// Colours for circles and arrows.
+const GREEN_BRUSH: Color = Color::rgba(0.08, 0.47, 0.11, 0.8);
+const RED_BRUSH: Color = Color::rgba(0.53, 0.13, 0.13, 0.8);
+const BLUE_BRUSH: Color = Color::rgba(0., 0.19, 0.53, 0.8);
+const YELLOW_BRUSH: Color = Color::rgba(0.9, 0.56, 0., 0.8);
+const MOVE_HINT: Color = Color::rgba(0.08, 0.33, 0.13, 0.5);
+const BANNER: Color = Color::rgba(0.06, 0.06, 0.74, 0.85);
+pub const FONT: &str = "fonts/NotoSans-Bold.ttf";
 // Notation strings
-const RANKS: &str = "12345678";
-const FILES: &str = "abcdefgh";
+pub const RANKS: &str = "12345678";
+pub const FILES: &str = "abcdefgh";
 
 struct PieceDragEvent(Entity, Position);
 struct PieceDropEvent(Entity);
-#[derive(Debug)]
-struct DeletePieceEvent(Entity);
 struct DrawPieceEvent(Entity);
 struct DrawMarkerEvent(Position);
+struct GameOverEvent(PieceColor, Victory);
 
 // This system writes the files and rank numbers.
 // At present, it does not scale to window size. I need to think of an algorithm that might solve this problem...
@@ -153,7 +175,7 @@ fn draw_notation(mut commands: Commands, asset_server: Res<AssetServer>) {
                 text: Text::with_section(
                     file,
                     TextStyle {
-                        font: asset_server.load("fonts\\NotoSans-Bold.ttf"),
+                        font: asset_server.load(FONT),
                         font_size: 18.,
                         color: {
                             if i % 2 == 0 {
@@ -175,7 +197,7 @@ fn draw_notation(mut commands: Commands, asset_server: Res<AssetServer>) {
                 text: Text::with_section(
                     rank,
                     TextStyle {
-                        font: asset_server.load("fonts\\NotoSans-Bold.ttf"),
+                        font: asset_server.load(FONT),
                         font_size: 18.,
                         color: {
                             if i % 2 == 0 {
@@ -197,7 +219,9 @@ fn draw_notation(mut commands: Commands, asset_server: Res<AssetServer>) {
 // Spawns camera bundle;
 // Create square entities, with position and size.
 fn setup_board(mut commands: Commands) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    let mut camera = OrthographicCameraBundle::new_2d();
+    camera.transform.translation.x = PANEL_WIDTH / 2.;
+    commands.spawn_bundle(camera);
 
     for x in 0..8 {
         for y in 0..8 {
@@ -222,31 +246,146 @@ fn setup_board(mut commands: Commands) {
     }
 }
 
-// Dummy function that draws 8 pawns.
+// Sets up the board from the position given by `--fen "<FEN2>"` on the command line, or the regular starting position.
+// The sprites themselves are spawned by sync_piece_sprites() once the board has changed, same as for draw_piece().
+// Asking for a Fischer random position with `--fischer <number>` takes precedence.
+fn draw_piece_setup(mut board: ResMut<BoardState>, startup: Res<StartupArgs>) {
+    if let Some(id) = startup.fischer.as_deref().and_then(fischer_arg) {
+        setup_fischer_random(&mut board, id);
+        return;
+    }
+
+    let fen = startup.fen.as_deref().unwrap_or(STARTING_FEN2);
+    match from_fen2(fen) {
+        Ok(parsed) => *board = parsed,
+        Err(e) => error!("Couldn't set up the board from '{}': {}", fen, e),
+    }
+}
+
 // Spawn piece entity with Position and PieceSize.
 // To be honest, I'm not entirely sure that Piece needs to have Position as a field here.
-fn draw_piece_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    for x in 0..8 {
+fn spawn_piece(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    window: &Window,
+    pos: Position,
+    piece: &BoardPiece,
+    flipped: &Flipped,
+) {
+    let screen = screen_square(pos, flipped);
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: asset_server.load(&get_piece_filename(piece)),
+            transform: Transform {
+                translation: Vec3::new(
+                    convert(screen.x as f32, board_size(window), 8f32),
+                    convert(screen.y as f32, board_size(window), 8f32),
+                    2.0,
+                ),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Piece { pos })
+        .insert(pos)
+        .insert(PieceSize::size(0.67));
+}
+
+// The piece sprites are only a view of the BoardState - whenever the board changes, they're all thrown away and redrawn.
+fn sync_piece_sprites(
+    mut commands: Commands,
+    board: Res<BoardState>,
+    asset_server: Res<AssetServer>,
+    windows: Res<Windows>,
+    flipped: Res<Flipped>,
+    sprites: Query<Entity, With<Piece>>,
+) {
+    if !board.is_changed() && !flipped.is_changed() {
+        return;
+    }
+
+    let window = windows.get_primary().unwrap();
+    for ent in sprites.iter() {
+        commands.entity(ent).despawn();
+    }
+    for (pos, piece) in board.pieces() {
+        spawn_piece(&mut commands, &asset_server, window, pos, &piece, &flipped);
+    }
+}
+
+// Whenever the board changes, check whether somebody has just won.
+fn detect_game_over(
+    board: Res<BoardState>,
+    mut last_result: Local<Option<(PieceColor, Victory)>>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
+) {
+    if !board.is_changed() {
+        return;
+    }
+
+    let result = winner(&board);
+    if result != *last_result {
+        if let Some((color, victory)) = result {
+            info!("{} wins by {}", color, victory);
+            ev_game_over.send(GameOverEvent(color, victory));
+        }
+        *last_result = result;
+    }
+}
+
+// Puts a banner over the midline when the game ends, and takes it down again once the position is back in play.
+fn game_over_banner(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    board: Res<BoardState>,
+    mut ev_game_over: EventReader<GameOverEvent>,
+    banners: Query<Entity, With<Banner>>,
+) {
+    let game_over = ev_game_over.iter().last();
+    if game_over.is_none() && !(board.is_changed() && winner(&board).is_none()) {
+        return;
+    }
+    for banner in banners.iter() {
+        commands.entity(banner).despawn();
+    }
+
+    if let Some(GameOverEvent(color, victory)) = game_over {
         commands
             .spawn_bundle(SpriteBundle {
-                texture: asset_server.load("pieces\\nemesis\\p.png"),
+                sprite: Sprite {
+                    color: BANNER,
+                    ..default()
+                },
+                transform: Transform {
+                    translation: Vec3::new(0., 0., 5.),
+                    ..default()
+                },
                 ..default()
             })
-            .insert(Piece {
-                pos: Position { x, y: 6 },
-            })
-            .insert(Position { x, y: 6 })
-            .insert(PieceSize::size(0.67));
+            .insert(Banner)
+            .insert(Size { width: 8., height: 1. });
 
         commands
-            .spawn_bundle(SpriteBundle {
-                texture: asset_server.load("pieces\\classic\\p_w.png"),
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    format!("{} wins by {}", color, victory),
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: 36.,
+                        color: LIGHT,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                transform: Transform {
+                    translation: Vec3::new(0., 0., 6.),
+                    ..default()
+                },
                 ..default()
             })
-            .insert(Piece {
-                pos: Position { x, y: 1 },
-            })
-            .insert(PieceSize::size(0.67));
+            .insert(Banner);
     }
 }
 
@@ -274,8 +413,8 @@ fn size_scaling(windows: Res<Windows>, mut q: Query<(&Size, &mut Transform)>) {
     // This function is where the magic happens, since we're on a 8x8 board we divide by 8
     for (sprite_size, mut transform) in q.iter_mut() {
         transform.scale = Vec3::new(
-            sprite_size.width / 8f32 * window.width() as f32,
-            sprite_size.height / 8f32 * window.height() as f32,
+            sprite_size.width / 8f32 * board_size(window) as f32,
+            sprite_size.height / 8f32 * board_size(window) as f32,
             0.,
         );
     }
@@ -287,8 +426,8 @@ fn piece_size_scaling(windows: Res<Windows>, mut q: Query<(&PieceSize, &mut Tran
     let window = windows.get_primary().unwrap();
     for (sprite_size, mut transform) in q.iter_mut() {
         transform.scale = Vec3::new(
-            sprite_size.width / 8f32 * (window.width() as f32 / 96.),
-            sprite_size.height / 8f32 * (window.height() as f32 / 96.),
+            sprite_size.width / 8f32 * (board_size(window) as f32 / 96.),
+            sprite_size.height / 8f32 * (board_size(window) as f32 / 96.),
             0.,
         );
     }
@@ -303,13 +442,15 @@ fn convert(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
 // There's some black magic going on here with how the entities' positions in-game are translated to the Position struct.
 fn position_translation(
     windows: Res<Windows>,
+    flipped: Res<Flipped>,
     mut q: Query<(&Position, &mut Transform, With<Square>)>,
 ) {
     let window = windows.get_primary().unwrap();
     for (pos, mut transform, _square) in q.iter_mut() {
+        let pos = screen_square(*pos, &flipped);
         transform.translation = Vec3::new(
-            convert(pos.x as f32, window.width() as f32, 8f32),
-            convert(pos.y as f32, window.height() as f32, 8f32),
+            convert(pos.x as f32, board_size(window) as f32, 8f32),
+            convert(pos.y as f32, board_size(window) as f32, 8f32),
             0.0,
         );
     }
@@ -318,22 +459,26 @@ fn position_translation(
 //Do the same for highlights.
 fn highlight_position_translation(
     windows: Res<Windows>,
+    flipped: Res<Flipped>,
     mut q: Query<(&Position, &mut Transform, With<HighlightSquare>)>,
 ) {
     let window = windows.get_primary().unwrap();
     for (pos, mut transform, _square) in q.iter_mut() {
+        let pos = screen_square(*pos, &flipped);
         transform.translation = Vec3::new(
-            convert(pos.x as f32, window.width() as f32, 8f32),
-            convert(pos.y as f32, window.height() as f32, 8f32),
+            convert(pos.x as f32, board_size(window) as f32, 8f32),
+            convert(pos.y as f32, board_size(window) as f32, 8f32),
             1.0,
         );
     }
 }
 
 // We need to use a similar function for notation as above...
+// The files stay along the bottom and the ranks down the right-hand side when the board is flipped, just in the other order.
 fn notation_position_translation(
     windows: Res<Windows>,
-    mut q: Query<(&Position, &mut Transform, &FilesRanks)>,
+    flipped: Res<Flipped>,
+    mut q: Query<(&Position, &mut Transform, &mut Text, &FilesRanks)>,
 ) {
     // Overwrites the primary convert() function cos we need to include FilesRanks
     fn convert(pos: f32, bound_window: f32, bound_game: f32, notation_offset: &FilesRanks) -> f32 {
@@ -352,10 +497,17 @@ fn notation_position_translation(
     }
 
     let window = windows.get_primary().unwrap();
-    for (pos, mut transform, notation) in q.iter_mut() {
+    for (pos, mut transform, mut text, notation) in q.iter_mut() {
+        let turned = screen_square(*pos, &flipped);
+        let pos = match notation {
+            FilesRanks::File => Position::new(turned.x, pos.y),
+            FilesRanks::Rank => Position::new(pos.x, turned.y),
+        };
+        // Each letter and number is written in the colour of the squares it isn't on.
+        text.sections[0].style.color = if (pos.x + pos.y + 1) % 2 == 0 { DARK } else { LIGHT };
         transform.translation = Vec3::new(
-            convert(pos.x as f32, window.width() as f32, 8f32, notation),
-            convert(pos.y as f32, window.height() as f32, 8f32, notation),
+            convert(pos.x as f32, board_size(window) as f32, 8f32, notation),
+            convert(pos.y as f32, board_size(window) as f32, 8f32, notation),
             2.0,
         );
     }
@@ -374,72 +526,79 @@ fn move_piece_system(
     mut cursor_moved_event_reader: EventReader<CursorMoved>,
     mouse_button_input: Res<Input<MouseButton>>,
     mut sprites: Query<(Entity, &Sprite, With<PieceSize>)>,
-    mut pieces: Query<(Entity, &mut Piece)>,
+    pieces: Query<&Piece>,
     mut transforms: Query<&mut Transform>,
     mut highlight_q: Query<(Entity, &HighlightSquare, Without<Marker>)>,
-    mut ev_delete: EventWriter<DeletePieceEvent>,
-    mut is_holding_piece: Local<bool>, // Checks for held pieces when mouse pressed/released - prevents piece deletion via accidental empty-dragging
+    mut board: ResMut<BoardState>,
+    mut duel: ResMut<Duel>,
+    // Bevy stops at 16 system parameters, so the game's record and what's drawn on it share one.
+    (mut history, mut annotations): (ResMut<GameHistory>, ResMut<Annotations>),
+    cursor_state: Res<CurrentState<CursorState>>,
+    flipped: Res<Flipped>,
+    mut promotion: ResMut<PendingPromotion>,
 ) {
     let window = windows.get_primary().unwrap();
-    let tile_size = window.width() / 8.;
-    let half_window = Vec2::new(window.width() / 2., window.height() / 2.);
+    let tile_size = board_size(window) / 8.;
+    let half_board = Vec2::splat(board_size(window) / 2.);
     if let Some(cursor_ev) = cursor_moved_event_reader.iter().last() {
-        state.cursor_pos = cursor_ev.position - half_window;
-        state.cursor_grid_pos = Position {
+        state.cursor_pos = cursor_ev.position - half_board;
+        state.cursor_grid_pos = screen_square(Position {
             x: (cursor_ev.position.x / tile_size) as i32,
             y: (cursor_ev.position.y / tile_size) as i32,
-        };
+        }, &flipped);
     };
+    let playing = cursor_state.0 == CursorState::Play;
 
     if mouse_button_input.just_released(MouseButton::Left) {
-        if *is_holding_piece {
-            for (ent, piece) in pieces.iter() {
-                if piece.pos == state.cursor_grid_pos {
-                    warn!("Piece deleted at: ({}, {})", piece.pos.x, piece.pos.y);
-                    ev_delete.send(DeletePieceEvent(ent));
+        if let Some(sprite) = state.sprite {
+            // The sprite still knows which square it was picked up from.
+            if let Ok(piece) = pieces.get(sprite.0) {
+                if playing {
+                    // In a game, the drop has to be a legal move, which the rules engine then plays - captures and all.
+                    // Anything else just snaps back. Whirlwinds are left to the middle mouse button,
+                    // so putting a king back down where it was doesn't set one off. Castling is dropping the king on its rook (see drop_moves).
+                    // A pawn reaching the last rank has a move per piece it can become, so the player gets asked which.
+                    let moves = drop_moves(&board, piece.pos, state.cursor_grid_pos);
+                    match moves.as_slice() {
+                        [] => info!("Illegal move: {} to {}", piece.pos.to_algebraic(), state.cursor_grid_pos.to_algebraic()),
+                        [mv] => {
+                            play_move_from_board(&mut board, &mut duel, &mut history, &mut annotations, mv);
+                        }
+                        _ if moves.iter().all(|mv| mv.promotion.is_some()) => promotion.0 = moves,
+                        _ => warn!("{} to {} could be more than one move", piece.pos.to_algebraic(), state.cursor_grid_pos.to_algebraic()),
+                    }
+                } else if piece.pos != state.cursor_grid_pos {
+                    // Moving the piece on the board overwrites whatever was on the target square, and the sprites get redrawn afterwards.
+                    if let Some(captured) = board.move_piece(piece.pos, state.cursor_grid_pos) {
+                        warn!("Piece deleted at: ({}, {}): {:?}", state.cursor_grid_pos.x, state.cursor_grid_pos.y, captured);
+                    }
+                    if !annotations.is_empty() {
+                        *annotations = Annotations::default();
+                    }
                 }
             }
-        }
-
-
-
-        if let Some(sprite) = state.sprite {
-            let mut sprite_pos = transforms.get_mut(sprite.0).unwrap();
-            let mut piece_internal = pieces.get_mut(sprite.0).unwrap();
-            sprite_pos.translation = Vec3::new(
-                convert(state.cursor_grid_pos.x as f32, window.width() as f32, 8f32),
-                convert(state.cursor_grid_pos.y as f32, window.height() as f32, 8f32),
-                2.0,
-            );
-
-            piece_internal.1.pos.x = state.cursor_grid_pos.x;
-            piece_internal.1.pos.y = state.cursor_grid_pos.y;
+            // Snap the sprite back into place, even if the piece didn't go anywhere.
+            board.set_changed();
 
             info!(
                 "Piece position on grid: ({}, {})",
                 state.cursor_grid_pos.x, state.cursor_grid_pos.y
             );
 
-    
-
-            let hl = highlight_q.single_mut();
-            ev_drop.send(PieceDropEvent(hl.0));
             state.sprite = None;
-            *is_holding_piece = false;
+            if let Ok(hl) = highlight_q.get_single_mut() {
+                ev_drop.send(PieceDropEvent(hl.0));
+            }
             return;
         }
     }
     if mouse_button_input.pressed(MouseButton::Left) && state.sprite.is_some() {
         let sprite = state.sprite.unwrap();
-        let mut piece_internal = pieces.get_mut(sprite.0).unwrap();
-        piece_internal.1.pos.x = -1;
-        piece_internal.1.pos.y = -1;
-
-        let mut sprite_pos = transforms.get_mut(sprite.0).unwrap();
-
-        sprite_pos.translation.x = state.cursor_pos.x;
-        sprite_pos.translation.y = state.cursor_pos.y;
-        sprite_pos.translation.z = 4.0;
+        if let Ok(mut sprite_pos) = transforms.get_mut(sprite.0) {
+            sprite_pos.translation.x = state.cursor_pos.x;
+            sprite_pos.translation.y = state.cursor_pos.y;
+            sprite_pos.translation.z = 4.0;
+        }
     }
 
     if mouse_button_input.just_pressed(MouseButton::Left) {
@@ -452,96 +611,240 @@ fn move_piece_system(
                     tile_size,
                     tile_size
                 ));
-            if diff.length() < (sprite_size.x / 2.0) {
+            let can_move = || match pieces.get(entity) {
+                Ok(piece) => !playing || (can_play(&board, &duel, piece.pos) && promotion.0.is_empty()),
+                Err(_) => false,
+            };
+            if diff.length() < (sprite_size.x / 2.0) && can_move() {
                 state.sprite = Some((entity, diff));
                 info!(
                     "Piece picked up on: ({}, {})",
                     state.cursor_grid_pos.x, state.cursor_grid_pos.y
                 );
-                *is_holding_piece = true;
+                if let Ok(piece) = pieces.get(entity) {
+                    let destinations: Vec<String> = legal_destinations(&board, piece.pos).iter().map(|pos| pos.to_algebraic()).collect();
+                    info!("Legal moves: {}", destinations.join(", "));
+                }
                 ev_drag.send(PieceDragEvent(entity, state.cursor_grid_pos));
             }
         }
     }
 }
 
+// Whether the piece on `pos` may move in a game: it has to be its side's turn, with no duel to settle and nobody having won yet.
+fn can_play(board: &BoardState, duel: &Duel, pos: Position) -> bool {
+    board.get(pos).is_some_and(|piece| piece.color == board.side_to_move)
+        && *duel == Duel::Idle
+        && winner(board).is_none()
+}
+
+// Middle-clicking a Two Kings king sets off its whirlwind, if it has one.
+fn whirlwind_system(
+    windows: Res<Windows>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    mut board: ResMut<BoardState>,
+    mut duel: ResMut<Duel>,
+    // Paired up as in move_piece_system.
+    (mut history, mut annotations): (ResMut<GameHistory>, ResMut<Annotations>),
+    cursor_state: Res<CurrentState<CursorState>>,
+    flipped: Res<Flipped>,
+) {
+    if !mouse_button_input.just_pressed(MouseButton::Middle) {
+        return;
+    }
+    let window = windows.get_primary().unwrap();
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let tile_size = board_size(window) / 8.;
+    let cursor_grid_pos = screen_square(Position {
+        x: (cursor.x / tile_size) as i32,
+        y: (cursor.y / tile_size) as i32,
+    }, &flipped);
+
+    let whirlwind = legal_moves(&board, cursor_grid_pos)
+        .into_iter()
+        .find(|mv| mv.kind == MoveKind::Whirlwind);
+    let mv = match whirlwind {
+        Some(mv) => mv,
+        None => return,
+    };
+    if cursor_state.0 == CursorState::Play {
+        if can_play(&board, &duel, cursor_grid_pos) {
+            let captured = play_move_from_board(&mut board, &mut duel, &mut history, &mut annotations, &mv);
+            warn!("Whirlwind at {} took {} pieces", mv.from.to_algebraic(), captured.len());
+        }
+    } else {
+        // Setting up a position, the pieces around the king just go - it isn't a move, so nobody's turn passes and nothing's written down.
+        let captured = mv.captures.iter().filter_map(|square| board.remove(*square)).count();
+        warn!("Whirlwind at {} cleared {} pieces", mv.from.to_algebraic(), captured);
+    }
+}
+
 // fn piece_position_translation(windows: Res<Windows>, mut q: Query<(&Position, &mut Transform, With<Piece>)>) {
-fn piece_position_translation(windows: Res<Windows>, mut q: Query<(&Piece, &mut Transform)>) {
+fn piece_position_translation(windows: Res<Windows>, flipped: Res<Flipped>, mut q: Query<(&Piece, &mut Transform)>) {
     let window = windows.get_primary().unwrap();
     for (piece, mut transform) in q.iter_mut() {
+        let pos = screen_square(piece.pos, &flipped);
         transform.translation = Vec3::new(
-            convert(piece.pos.x as f32, window.width() as f32, 8f32),
-            convert(piece.pos.y as f32, window.height() as f32, 8f32),
+            convert(pos.x as f32, board_size(window) as f32, 8f32),
+            convert(pos.y as f32, board_size(window) as f32, 8f32),
             2.0,
         );
     }
 }
 
 fn delete_piece(
-    mut ev_delete: EventWriter<DeletePieceEvent>,
     mut state: Local<CursorPos>,
     windows: Res<Windows>,
     mut cursor_moved_event_reader: EventReader<CursorMoved>,
     mouse_button_input: Res<Input<MouseButton>>,
-    pieces: Query<(Entity, &Piece)>,
+    mut board: ResMut<BoardState>,
+    flipped: Res<Flipped>,
 ) {
     let window = windows.get_primary().unwrap();
-    let tile_size = window.width() / 8.;
-    let half_window = Vec2::new(window.width() / 2., window.height() / 2.);
+    let tile_size = board_size(window) / 8.;
+    let half_board = Vec2::splat(board_size(window) / 2.);
     if let Some(cursor_ev) = cursor_moved_event_reader.iter().last() {
-        state.cursor_pos = cursor_ev.position - half_window;
-        state.cursor_grid_pos = Position {
+        state.cursor_pos = cursor_ev.position - half_board;
+        state.cursor_grid_pos = screen_square(Position {
             x: (cursor_ev.position.x / tile_size) as i32,
             y: (cursor_ev.position.y / tile_size) as i32,
-        };
+        }, &flipped);
     };
 
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        for (ent, piece) in pieces.iter() {
-            if piece.pos == state.cursor_grid_pos {
-                warn!("Piece deleted at: ({}, {})", piece.pos.x, piece.pos.y);
-                ev_delete.send(DeletePieceEvent(ent));
-            }
-        }
+    if mouse_button_input.just_pressed(MouseButton::Left) && board.get(state.cursor_grid_pos).is_some() {
+        board.remove(state.cursor_grid_pos);
+        warn!("Piece deleted at: ({}, {})", state.cursor_grid_pos.x, state.cursor_grid_pos.y);
     }
 }
 
-// This system listens for a right-click and then sends a DrawMarkerEvent to draw_marker().
-// When it receives a left-click, it sends an EraseMarkerEvent, which is heard by erase_markers()
+// This system listens for a right-click and marks the square, or draws an arrow if the mouse was dragged to another square first.
+// Drawing the same arrow again rubs it out. Left-clicking anywhere clears the lot, over in erase_highlight().
 fn marker_system(
-    mut commands: Commands,
-    mut state: Local<CursorPos>,
+    mut annotations: ResMut<Annotations>,
+    mut drag_start: Local<Option<(Position, Brush)>>,
     windows: Res<Windows>,
-    mut cursor_moved_event_reader: EventReader<CursorMoved>,
+    kbd: Res<Input<KeyCode>>,
     mouse_button_input: Res<Input<MouseButton>>,
+    flipped: Res<Flipped>,
 ) {
     let window = windows.get_primary().unwrap();
-    let tile_size = window.width() / 8.;
-    let half_window = Vec2::new(window.width() / 2., window.height() / 2.);
-    if let Some(cursor_ev) = cursor_moved_event_reader.iter().last() {
-        state.cursor_pos = cursor_ev.position - half_window;
-        state.cursor_grid_pos = Position {
-            x: (cursor_ev.position.x / tile_size) as i32,
-            y: (cursor_ev.position.y / tile_size) as i32,
-        };
+    let tile_size = board_size(window) / 8.;
+    let cursor_grid_pos = match window.cursor_position() {
+        Some(cursor) => screen_square(Position {
+            x: (cursor.x / tile_size) as i32,
+            y: (cursor.y / tile_size) as i32,
+        }, &flipped),
+        None => return,
     };
 
+    // The colour is picked when the button goes down, like on Lichess.
     if mouse_button_input.just_pressed(MouseButton::Right) {
+        let brush = if kbd.pressed(KeyCode::LShift) || kbd.pressed(KeyCode::RShift) {
+            Brush::Red
+        } else if kbd.pressed(KeyCode::LAlt) || kbd.pressed(KeyCode::RAlt) {
+            Brush::Blue
+        } else if kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl) {
+            Brush::Yellow
+        } else {
+            Brush::Green
+        };
+        *drag_start = Some((cursor_grid_pos, brush));
+    }
+    if mouse_button_input.just_released(MouseButton::Right) {
+        let to = cursor_grid_pos;
+        match drag_start.take() {
+            Some((from, _)) if !to.in_bounds() || !from.in_bounds() => {}
+            Some((from, brush)) if from == to => annotations.toggle_marker(MarkedSquare { square: to, brush }),
+            Some((from, brush)) => annotations.toggle_arrow(Arrow { from, to, brush }),
+            None => {}
+        }
+    }
+}
+
+// Like the pieces, the markers and arrows are redrawn from scratch whenever the annotations change.
+fn sync_markers(
+    mut commands: Commands,
+    annotations: Res<Annotations>,
+    windows: Res<Windows>,
+    flipped: Res<Flipped>,
+    marker_q: Query<Entity, With<Marker>>,
+    arrow_q: Query<Entity, With<ArrowShape>>,
+) {
+    if !annotations.is_changed() && !flipped.is_changed() {
+        return;
+    }
+
+    for marker in marker_q.iter().chain(arrow_q.iter()) {
+        commands.entity(marker).despawn();
+    }
+    let window = windows.get_primary().unwrap();
+    for arrow in &annotations.arrows {
+        draw_arrow(&mut commands, window, &flipped, arrow);
+    }
+    let tile_size = board_size(window) / 8.;
+    for marker in &annotations.markers {
+        let centre = square_centre(marker.square, window, &flipped);
         commands
-            .spawn_bundle(SpriteBundle {
-                sprite: Sprite {
-                    color: MARKER,
-                    ..default()
-                },
-                ..default()
-            })
-            .insert(HighlightSquare)
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Circle { radius: tile_size * CIRCLE_RADIUS, ..default() },
+                DrawMode::Stroke(StrokeMode::new(brush_color(marker.brush), tile_size * CIRCLE_WIDTH)),
+                Transform::from_xyz(centre.x, centre.y, 3.5),
+            ))
             .insert(Marker)
-            .insert(Position {x: state.cursor_grid_pos.x, y: state.cursor_grid_pos.y})
-            .insert(Size::square(1.));
+            .insert(marker.square);
+    }
+}
+
+pub fn brush_color(brush: Brush) -> Color {
+    match brush {
+        Brush::Green => GREEN_BRUSH,
+        Brush::Red => RED_BRUSH,
+        Brush::Blue => BLUE_BRUSH,
+        Brush::Yellow => YELLOW_BRUSH,
     }
 }
 
+// The middle of a square, where it's drawn in the window.
+fn square_centre(pos: Position, window: &Window, flipped: &Flipped) -> Vec2 {
+    let pos = screen_square(pos, flipped);
+    Vec2::new(convert(pos.x as f32, board_size(window), 8.), convert(pos.y as f32, board_size(window), 8.))
+}
+
+// An arrow is a line from the middle of one square towards the middle of the other, with a triangle for a head.
+fn draw_arrow(commands: &mut Commands, window: &Window, flipped: &Flipped, arrow: &Arrow) {
+    let tile_size = board_size(window) / 8.;
+    let (from, to) = (square_centre(arrow.from, window, flipped), square_centre(arrow.to, window, flipped));
+    let (points, head) = arrow.outline(from, to, tile_size);
+
+    let mut line = PathBuilder::new();
+    line.move_to(points[0]);
+    for point in &points[1..] {
+        line.line_to(*point);
+    }
+    let head = shapes::Polygon {
+        points: head.to_vec(),
+        closed: true,
+    };
+
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &line.build(),
+            DrawMode::Stroke(StrokeMode::new(brush_color(arrow.brush), tile_size * ARROW_WIDTH)),
+            Transform::from_xyz(0., 0., 3.5),
+        ))
+        .insert(ArrowShape);
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &head,
+            DrawMode::Fill(FillMode::color(brush_color(arrow.brush))),
+            Transform::from_xyz(0., 0., 3.5),
+        ))
+        .insert(ArrowShape);
+}
+
 fn draw_highlight(mut commands: Commands, mut ev_draw_highlight: EventReader<PieceDragEvent>) {
     if let Some(ev) = ev_draw_highlight.iter().last() {
         commands
@@ -558,30 +861,102 @@ fn draw_highlight(mut commands: Commands, mut ev_draw_highlight: EventReader<Pie
     }
 }
 
-fn erase_highlight(
+// Mark every square the picked-up piece can go to: a dot for a quiet move, and a ring for a capture.
+// A move that captures something somewhere else (en passant, a rampage) still gets a ring on the square it lands on.
+fn draw_move_hints(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    board: Res<BoardState>,
+    flipped: Res<Flipped>,
+    mut ev_drag: EventReader<PieceDragEvent>,
+) {
+    if let Some(ev) = ev_drag.iter().last() {
+        let window = windows.get_primary().unwrap();
+        let tile_size = board_size(window) / 8.;
+
+        let mut hints: Vec<(Position, bool)> = Vec::new();
+        for mv in legal_moves(&board, ev.1) {
+            let capture = !mv.captures.is_empty();
+            match hints.iter_mut().find(|(square, _)| *square == mv.to) {
+                Some(hint) => hint.1 |= capture,
+                None => hints.push((mv.to, capture)),
+            }
+        }
+
+        for (square, capture) in hints {
+            let (shape, mode) = if capture {
+                (
+                    shapes::Circle { radius: tile_size * 0.45, ..default() },
+                    DrawMode::Stroke(StrokeMode::new(MOVE_HINT, tile_size * 0.08)),
+                )
+            } else {
+                (
+                    shapes::Circle { radius: tile_size * 0.15, ..default() },
+                    DrawMode::Fill(FillMode::color(MOVE_HINT)),
+                )
+            };
+            let screen = screen_square(square, &flipped);
+            let translation = Vec3::new(
+                convert(screen.x as f32, board_size(window), 8.),
+                convert(screen.y as f32, board_size(window), 8.),
+                3.0,
+            );
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(&shape, mode, Transform::from_translation(translation)))
+                .insert(MoveHint)
+                .insert(square);
+        }
+    }
+}
+
+fn erase_move_hints(
     mut commands: Commands,
     mut ev_drop: EventReader<PieceDropEvent>,
-    mouse_button_input: Res<Input<MouseButton>>,
-    marker_q: Query<(Entity, &HighlightSquare, With<Marker>)>
-    
+    hints: Query<Entity, With<MoveHint>>,
 ) {
-    if mouse_button_input.pressed(MouseButton::Left) {
-        for (marker, _highlight, _is_marker) in marker_q.iter() {
-            commands.entity(marker).despawn();
-        } 
+    if ev_drop.iter().last().is_some() {
+        for hint in hints.iter() {
+            commands.entity(hint).despawn();
+        }
     }
-    
+}
+
+fn erase_highlight(mut commands: Commands, mut ev_drop: EventReader<PieceDropEvent>) {
     for ev in ev_drop.iter() {
         commands.entity(ev.0).despawn();
     }
 }
 
-fn delete_piece_listener(
-    mut commands: Commands,
-    mut ev_delete_piece_listener: EventReader<DeletePieceEvent>,
+// Like on Lichess, left-clicking an empty square rubs out the circles and arrows - picking a piece up doesn't,
+// but playing the move does (see move_piece_system). Not on the move list, which would otherwise
+// wipe the drawing before the move list got to save it.
+fn erase_annotations(
+    windows: Res<Windows>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    board: Res<BoardState>,
+    cursor_state: Res<CurrentState<CursorState>>,
+    flipped: Res<Flipped>,
+    mut annotations: ResMut<Annotations>,
 ) {
-    for ev in ev_delete_piece_listener.iter() {
-        commands.entity(ev.0).despawn();
+    if !mouse_button_input.just_pressed(MouseButton::Left) || annotations.is_empty() {
+        return;
+    }
+    // Drawing or deleting pieces isn't playing a move.
+    if !matches!(cursor_state.0, CursorState::DragDrop | CursorState::Play) {
+        return;
+    }
+    let window = windows.get_primary().unwrap();
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let tile_size = board_size(window) / 8.;
+    let square = screen_square(Position {
+        x: (cursor.x / tile_size) as i32,
+        y: (cursor.y / tile_size) as i32,
+    }, &flipped);
+    if square.in_bounds() && board.get(square).is_none() {
+        *annotations = Annotations::default();
     }
 }
 
@@ -598,9 +973,9 @@ fn change_menu(
     if (kbd.just_pressed(KeyCode::Key0) ^ kbd.just_pressed(KeyCode::Numpad0)) && !(btn.pressed(MouseButton::Left)) {
         commands.insert_resource(NextState(CursorState::Trash));
     }
-    // if (kbd.just_pressed(KeyCode::Key9) ^ kbd.just_pressed(KeyCode::Numpad9)) && !(btn.pressed(MouseButton::Left)) {
-    //     commands.insert_resource(NextState(CursorState::ChangeArmy));
-    // }
+    if (kbd.just_pressed(KeyCode::Key9) ^ kbd.just_pressed(KeyCode::Numpad9)) && !(btn.pressed(MouseButton::Left)) {
+        commands.insert_resource(NextState(CursorState::Play));
+    }
 
     // Piece-placing options
 
@@ -648,10 +1023,12 @@ fn change_drawable_color(
     }
 }
 
+/// Cycle the army of whichever colour is currently being drawn - each side keeps its own army.
+/// Pieces already on the board keep the army they were drawn with.
 fn change_armies(
-    mut commands: Commands, 
     kbd: Res<Input<KeyCode>>,
-    mut index: Local<usize>,
+    color_state: Res<CurrentState<PieceColor>>,
+    mut board: ResMut<BoardState>,
 ) {
     const ARMIES: &[ArmyStates] = &[
         ArmyStates::Classic,
@@ -662,25 +1039,46 @@ fn change_armies(
         ArmyStates::Animals,
     ];
 
+    let color = color_state.0;
+    let index = ARMIES.iter().position(|army| *army == board.army(color)).unwrap_or(0);
+
     if (kbd.just_pressed(KeyCode::A) || kbd.just_pressed(KeyCode::Left)) && !(kbd.just_pressed(KeyCode::D)) {
-        *index = if *index == 0 {
+        let index = if index == 0 {
             ARMIES.len() - 1
         } else {
-            *index - 1
+            index - 1
         };
-        commands.insert_resource(NextState(ARMIES[*index]));
+        board.set_army(color, ARMIES[index]);
+        info!("{} army is now: {:?}", color, ARMIES[index]);
     }
 
     if (kbd.just_pressed(KeyCode::D) || kbd.just_pressed(KeyCode::Right)) && !(kbd.just_pressed(KeyCode::A) || kbd.just_pressed(KeyCode::Left)) {
-        *index = (*index + 1) % ARMIES.len();
-        commands.insert_resource(NextState(ARMIES[*index]));
+        let index = (index + 1) % ARMIES.len();
+        board.set_army(color, ARMIES[index]);
+        info!("{} army is now: {:?}", color, ARMIES[index]);
     }
 
 }
 
-fn debug_current_army(state: Res<CurrentState<ArmyStates>>) {
-    if state.is_changed() {
-        info!("Current army is: {:?}", state);
+// Turn the board around on F, to see it from the other side.
+fn flip_board(kbd: Res<Input<KeyCode>>, mut flipped: ResMut<Flipped>) {
+    if kbd.just_pressed(KeyCode::F) {
+        flipped.0 = !flipped.0;
+        info!("{} is at the bottom.", if flipped.0 { PieceColor::Black } else { PieceColor::White });
+    }
+}
+
+/// Reset the board to the starting position on R, keeping the army each side has chosen.
+fn reset_board(
+    kbd: Res<Input<KeyCode>>,
+    btn: Res<Input<MouseButton>>,
+    mut board: ResMut<BoardState>,
+) {
+    let shift = kbd.pressed(KeyCode::LShift) || kbd.pressed(KeyCode::RShift);
+    if kbd.just_pressed(KeyCode::R) && !shift && !(btn.pressed(MouseButton::Left)) {
+        let (white, black) = (board.army(PieceColor::White), board.army(PieceColor::Black));
+        *board = BoardState::starting_position(white, black);
+        info!("Board reset: {:?} vs. {:?}", white, black);
     }
 }
 
@@ -697,58 +1095,35 @@ fn debug_current_drawable_color(state: Res<CurrentState<PieceColor>>) {
 }
 
 fn draw_piece(
-    mut commands: Commands,
     cursor_state: Res<CurrentState<CursorState>>,
-    army_state: Res<CurrentState<ArmyStates>>,
     color_state: Res<CurrentState<PieceColor>>,
-    asset_server: Res<AssetServer>,
     mut state: Local<CursorPos>,
     windows: Res<Windows>,
-    occupied_squares: Query<(Entity, &Piece)>,
-    mut cursor_moved_event_reader: EventReader<CursorMoved>,
-    mut ev_overwrite: EventWriter<DeletePieceEvent>,
     mouse_button_input: Res<Input<MouseButton>>,
-
+    mut board: ResMut<BoardState>,
+    flipped: Res<Flipped>,
 ) {
     let window = windows.get_primary().unwrap();
-    let tile_size = window.width() / 8.;
-    let half_window = Vec2::new(window.width() / 2., window.height() / 2.);
-    if let Some(cursor_ev) = cursor_moved_event_reader.iter().last() {
-        state.cursor_pos = cursor_ev.position - half_window;
-        state.cursor_grid_pos = Position {
-            x: (cursor_ev.position.x / tile_size) as i32,
-            y: (cursor_ev.position.y / tile_size) as i32,
-        };        
+    let tile_size = board_size(window) / 8.;
+    let half_board = Vec2::splat(board_size(window) / 2.);
+    // Read straight off the window, so a flip is picked up without having to move the mouse.
+    if let Some(cursor) = window.cursor_position() {
+        state.cursor_pos = cursor - half_board;
+        state.cursor_grid_pos = screen_square(Position {
+            x: (cursor.x / tile_size) as i32,
+            y: (cursor.y / tile_size) as i32,
+        }, &flipped);
     };
-   
+
 
     if mouse_button_input.just_pressed(MouseButton::Left) {
-        for (ent, piece) in occupied_squares.iter() {
-            if piece.pos == state.cursor_grid_pos {
-                warn!("Overwriting piece in position ({}, {})", piece.pos.x, piece.pos.y);
-                ev_overwrite.send(DeletePieceEvent(ent));
+        if let CursorState::Place(kind) = cursor_state.0 {
+            let piece = BoardPiece { kind, color: color_state.0, army: board.army(color_state.0) };
+            if board.set(state.cursor_grid_pos, piece).is_some() {
+                warn!("Overwriting piece in position ({}, {})", state.cursor_grid_pos.x, state.cursor_grid_pos.y);
             }
         }
 
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: draw_piece_match(army_state, color_state, cursor_state, asset_server),
-                transform: Transform {
-                    translation: Vec3::new(
-                        convert(state.cursor_grid_pos.x as f32, window.width() as f32, 8f32),
-                        convert(state.cursor_grid_pos.y as f32, window.height() as f32, 8f32),
-                        2.0,
-                    ),
-                    ..default()
-                },
-                ..default()
-            })
-            .insert(Piece {
-                pos: Position { x: state.cursor_grid_pos.x, y: state.cursor_grid_pos.y },
-            })
-            .insert(Position { x: state.cursor_grid_pos.x, y: state.cursor_grid_pos.y })
-            .insert(PieceSize::size(0.67));
-
             info!(
                 "Cursor position: ({}. {})", 
                 state.cursor_pos.x,
@@ -759,23 +1134,26 @@ fn draw_piece(
 }
 
 #[inline(always)]
-fn get_piece_filename(
-    army_state: &Res<CurrentState<ArmyStates>>,
-    color_state: &Res<CurrentState<PieceColor>>,
-    piece_letter: &str
-) -> String {
+pub fn get_piece_filename(piece: &BoardPiece) -> String {
     format!(
-        "pieces\\{}\\{}{}.png",
-        match army_state.0 {
+        "pieces/{}/{}{}.png",
+        match piece.army {
             ArmyStates::Classic => "classic",
             ArmyStates::Nemesis => "nemesis",
             ArmyStates::Empowered => "empowered",
             ArmyStates::Reaper => "reaper",
-            ArmyStates::TwoKings => "twoKings",
+            ArmyStates::TwoKings => "twokings",
             ArmyStates::Animals => "animals",
         },
-        piece_letter,
-        match color_state.0 {
+        match piece.kind {
+            PieceCursor::King => "k",
+            PieceCursor::Queen => "q",
+            PieceCursor::Rook => "r",
+            PieceCursor::Bishop => "b",
+            PieceCursor::Knight => "n",
+            PieceCursor::Pawn => "p",
+        },
+        match piece.color {
             PieceColor::White => "_w",
             PieceColor::Black => "",
         }
@@ -783,23 +1161,6 @@ fn get_piece_filename(
 
 }
 
-fn draw_piece_match(
-    army_state: Res<CurrentState<ArmyStates>>,
-    color_state: Res<CurrentState<PieceColor>>,
-    state: Res<CurrentState<CursorState>>,
-    asset_server: Res<AssetServer>,
-) -> Handle<Image> {
-
-    match state.0 {
-        CursorState::Place(PieceCursor::King) => asset_server.load(&get_piece_filename(&army_state, &color_state, "k")),
-        CursorState::Place(PieceCursor::Queen) => asset_server.load(&get_piece_filename(&army_state, &color_state, "q")),
-        CursorState::Place(PieceCursor::Rook) => asset_server.load(&get_piece_filename(&army_state, &color_state, "r")),
-        CursorState::Place(PieceCursor::Bishop) => asset_server.load(&get_piece_filename(&army_state, &color_state, "b")),
-        CursorState::Place(PieceCursor::Knight) => asset_server.load(&get_piece_filename(&army_state, &color_state, "n")),
-        CursorState::Place(PieceCursor::Pawn) | _ => asset_server.load(&get_piece_filename(&army_state, &color_state, "p")),
-    }
-}
-
 pub struct BoardPlugin;
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
@@ -809,7 +1170,8 @@ impl Plugin for BoardPlugin {
                 .with_system(setup_board.before(draw_midline).before(draw_notation))
                 .with_system(draw_midline.before(draw_notation).after(setup_board))
                 .with_system(draw_notation.after(setup_board).after(draw_midline))
-                .with_system(draw_piece_setup.after(draw_notation)),
+                .with_system(draw_piece_setup.after(draw_notation))
+                .with_system(draw_stone_counters),
         )
         .add_startup_system_set_to_stage(
             StartupStage::Startup,
@@ -821,33 +1183,73 @@ impl Plugin for BoardPlugin {
         .add_system_set_to_stage(
             CoreStage::PostUpdate,
             SystemSet::new()
+                .with_system(position_translation)
                 .with_system(notation_position_translation)
                 .with_system(highlight_position_translation.before(size_scaling))
                 .with_system(size_scaling.after(piece_size_scaling))
                 .with_system(piece_size_scaling)
                 .with_system(erase_highlight)
-                .with_system(change_armies),
+                .with_system(erase_annotations.run_if(pointer_on_board))
+                .with_system(erase_move_hints)
+                .with_system(change_armies)
+                .with_system(reset_board)
+                .with_system(fischer_random_board),
         )
+        .init_resource::<StartupArgs>()
+        .init_resource::<BoardState>()
+        .init_resource::<Duel>()
+        .init_resource::<GameHistory>()
+        .init_resource::<PointerOverPanel>()
+        .init_resource::<PendingPromotion>()
+        .init_resource::<Players>()
+        .init_resource::<Annotations>()
+        .init_resource::<Flipped>()
+        .add_system(flip_board)
+        .init_resource::<UndoStack>()
+        .add_system(undo_redo)
+        .add_system_to_stage(CoreStage::Last, track_edits)
+        .add_system(history_panel)
+        .add_system(promotion_prompt.before(history_panel))
+        .add_system(step_through_history)
+        .add_system(duel_input)
+        .add_system(update_stone_counters)
+        .add_system_to_stage(CoreStage::PreUpdate, sync_piece_sprites)
+        .add_system_to_stage(CoreStage::PreUpdate, sync_markers)
+        .add_system_to_stage(CoreStage::PreUpdate, detect_game_over)
+        .add_system_to_stage(CoreStage::PreUpdate, game_over_banner.after(detect_game_over))
         .add_loopless_state(CursorState::DragDrop)
-        .add_loopless_state(ArmyStates::Classic)
         .add_loopless_state(PieceColor::White)
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::DragDrop)
+                .run_if(pointer_on_board)
                 .with_system(move_piece_system)
-                .with_system(delete_piece_listener)
+                .with_system(whirlwind_system)
                 .with_system(change_drawable_color)
                 .with_system(draw_highlight)
+                .with_system(draw_move_hints)
                 // .with_system(change_armies)
                 .with_system(marker_system)
                 .with_system(change_menu)
                 .into()
         )
+        .add_system_set(
+            ConditionSet::new()
+                .run_in_state(CursorState::Play)
+                .run_if(pointer_on_board)
+                .with_system(move_piece_system)
+                .with_system(whirlwind_system)
+                .with_system(draw_highlight)
+                .with_system(draw_move_hints)
+                .with_system(marker_system)
+                .with_system(change_menu)
+                .into()
+        )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::Trash)
+                .run_if(pointer_on_board)
                 .with_system(delete_piece)
-                .with_system(delete_piece_listener)
                 .with_system(change_menu)
                 .with_system(change_drawable_color)
                 .into()
@@ -855,68 +1257,71 @@ impl Plugin for BoardPlugin {
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::Place(PieceCursor::King))
+                .run_if(pointer_on_board)
                 .with_system(draw_piece)
                 .with_system(change_menu)
                 .with_system(change_drawable_color)
-                .with_system(delete_piece_listener)
                 .into()
         )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::Place(PieceCursor::Queen))
+                .run_if(pointer_on_board)
                 .with_system(draw_piece)
                 .with_system(change_menu)
                 .with_system(change_drawable_color)
-                .with_system(delete_piece_listener)
                 .into()
         )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::Place(PieceCursor::Rook))
+                .run_if(pointer_on_board)
                 .with_system(draw_piece)
                 .with_system(change_menu)
                 .with_system(change_drawable_color)
-                .with_system(delete_piece_listener)
                 .into()
         )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::Place(PieceCursor::Bishop))
+                .run_if(pointer_on_board)
                 .with_system(draw_piece)
                 .with_system(change_menu)
                 .with_system(change_drawable_color)
-                .with_system(delete_piece_listener)
                 .into()
         )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::Place(PieceCursor::Knight))
+                .run_if(pointer_on_board)
                 .with_system(draw_piece)
                 .with_system(change_menu)
                 .with_system(change_drawable_color)
-                .with_system(delete_piece_listener)
                 .into()
         )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(CursorState::Place(PieceCursor::Pawn))
+                .run_if(pointer_on_board)
                 .with_system(draw_piece)
                 .with_system(size_scaling)
                 .with_system(change_menu)
                 .with_system(change_drawable_color)
-                .with_system(delete_piece_listener)
                 .into()
         )
         .add_system(debug_current_state)
-        .add_system(debug_current_army)
         .add_system(debug_current_drawable_color)
-        .add_system(take_screenshot)
+        .add_plugin(ScreenshotPlugin)
+        .add_system(export_fen2)
+        .add_system(import_fen2)
+        .add_system(export_pgn2)
+        .add_system(import_pgn2)
   
 
         .add_event::<PieceDragEvent>()
         .add_event::<PieceDropEvent>()
         .add_event::<DrawPieceEvent>()
-        .add_event::<DeletePieceEvent>()
+        .add_event::<GameOverEvent>()
         //.add_plugin(WorldInspectorPlugin::new())
         //.register_inspectable::<Position>()
         .run();
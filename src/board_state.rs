@@ -0,0 +1,448 @@
+use std::fmt;
+
+use bevy::prelude::Component;
+use bevy_inspector_egui::Inspectable;
+
+// A co-ordinate of an entity on the board.
+// Not the same as algebraic notation - (0, 0) is a1 and (7, 7) is h8 - but it converts to and from it for FEN2 and the rules.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Default, Inspectable, Debug)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+impl Position {
+    pub const fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    // Whether the co-ordinate lands on one of the 64 squares.
+    pub fn in_bounds(&self) -> bool {
+        (0..8).contains(&self.x) && (0..8).contains(&self.y)
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy)
+    }
+
+    pub fn to_algebraic(self) -> String {
+        format!("{}{}", (b'a' + self.x as u8) as char, self.y + 1)
+    }
+
+    pub fn from_algebraic(square: &str) -> Option<Self> {
+        let bytes = square.as_bytes();
+        if bytes.len() != 2 {
+            return None;
+        }
+        let pos = Self::new(bytes[0] as i32 - b'a' as i32, bytes[1] as i32 - b'1' as i32);
+        pos.in_bounds().then_some(pos)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceCursor {
+    King,
+    Queen,
+    Rook,
+    Bishop,
+    Knight,
+    Pawn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArmyStates {
+    Classic,
+    Nemesis,
+    Empowered,
+    Reaper,
+    TwoKings,
+    Animals
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceColor {
+    White,
+    Black,
+}
+impl fmt::Display for PieceColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PieceColor::White => write!(f, "White"),
+            PieceColor::Black => write!(f, "Black"),
+        }
+    }
+}
+
+impl PieceColor {
+    pub fn opposite(&self) -> Self {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+
+    // The rank this side's pieces start on.
+    pub fn home_rank(&self) -> i32 {
+        match self {
+            PieceColor::White => 0,
+            PieceColor::Black => 7,
+        }
+    }
+
+    // Which way this side's pawns walk up the board.
+    pub fn forward(&self) -> i32 {
+        match self {
+            PieceColor::White => 1,
+            PieceColor::Black => -1,
+        }
+    }
+}
+
+// Castling towards the h-file (short) or the a-file (long).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CastleSide {
+    Short,
+    Long,
+}
+
+// Both players start a game of Chess 2 with 3 duel stones, and can never hold more than 6.
+pub const STARTING_STONES: u8 = 3;
+pub const MAX_STONES: u8 = 6;
+
+// Every army lines up the same way - they just swap in their own pieces for the queen, rooks, etc.
+const BACK_RANK: [PieceCursor; 8] = [
+    PieceCursor::Rook,
+    PieceCursor::Knight,
+    PieceCursor::Bishop,
+    PieceCursor::Queen,
+    PieceCursor::King,
+    PieceCursor::Bishop,
+    PieceCursor::Knight,
+    PieceCursor::Rook,
+];
+
+// Everything there is to know about a piece, minus where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardPiece {
+    pub kind: PieceCursor,
+    pub color: PieceColor,
+    pub army: ArmyStates,
+}
+impl BoardPiece {
+    // Kings, plus the Two Kings army's Warrior King, which stands in for the queen.
+    // These are the pieces a side can't afford to lose.
+    pub fn is_royal(&self) -> bool {
+        self.kind == PieceCursor::King || (self.army == ArmyStates::TwoKings && self.kind == PieceCursor::Queen)
+    }
+}
+
+/// The position on the board, kept apart from the ECS so that anything can ask "what is on e4?".
+/// Systems edit this resource, and the piece sprites are rebuilt from it whenever it changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardState {
+    // Indexed as squares[x][y], same as Position.
+    squares: [[Option<BoardPiece>; 8]; 8],
+    pub side_to_move: PieceColor,
+    // The army each side is playing - White's, then Black's. Newly drawn pieces join their side's army.
+    armies: [ArmyStates; 2],
+    // White's stones, then Black's.
+    stones: [u8; 2],
+    // The file of the rook each side may still castle with, short side then long side.
+    // Files rather than yes/no, so Fischer random positions can castle too.
+    castling: [[Option<i32>; 2]; 2],
+    // The square a pawn skipped over with its double step last move, if it can be taken en passant.
+    pub en_passant: Option<Position>,
+    // Whether the last move walked a king across the midline, or took the last of a side's kings.
+    // A position that was only set up that way hasn't been won by anybody.
+    pub king_crossed: bool,
+    pub king_taken: bool,
+}
+
+impl Default for BoardState {
+    fn default() -> Self {
+        Self {
+            squares: Default::default(),
+            side_to_move: PieceColor::White,
+            armies: [ArmyStates::Classic; 2],
+            stones: [STARTING_STONES; 2],
+            castling: [[None; 2]; 2],
+            en_passant: None,
+            king_crossed: false,
+            king_taken: false,
+        }
+    }
+}
+
+impl BoardState {
+    /// The opening setup for a game between the two armies: back rank and pawns, White to move and 3 stones each.
+    pub fn starting_position(white_army: ArmyStates, black_army: ArmyStates) -> Self {
+        Self::with_back_rank(BACK_RANK, white_army, black_army)
+    }
+
+    // Same as above, but with the back rank shuffled around - both sides mirror each other, as in Fischer random.
+    pub fn with_back_rank(back_rank: [PieceCursor; 8], white_army: ArmyStates, black_army: ArmyStates) -> Self {
+        let mut board = BoardState::default();
+        board.set_army(PieceColor::White, white_army);
+        board.set_army(PieceColor::Black, black_army);
+        for (x, kind) in back_rank.iter().enumerate() {
+            let x = x as i32;
+            for (color, army, back_rank, pawn_rank) in [
+                (PieceColor::White, white_army, 0, 1),
+                (PieceColor::Black, black_army, 7, 6),
+            ] {
+                board.set(Position::new(x, back_rank), BoardPiece { kind: *kind, color, army });
+                board.set(Position::new(x, pawn_rank), BoardPiece { kind: PieceCursor::Pawn, color, army });
+            }
+        }
+        board.infer_castling();
+        board
+    }
+
+    pub fn get(&self, pos: Position) -> Option<BoardPiece> {
+        if pos.in_bounds() {
+            self.squares[pos.x as usize][pos.y as usize]
+        } else {
+            None
+        }
+    }
+
+    // Puts a piece on a square, handing back whatever was overwritten.
+    pub fn set(&mut self, pos: Position, piece: BoardPiece) -> Option<BoardPiece> {
+        if !pos.in_bounds() {
+            return None;
+        }
+        self.squares[pos.x as usize][pos.y as usize].replace(piece)
+    }
+
+    pub fn remove(&mut self, pos: Position) -> Option<BoardPiece> {
+        if !pos.in_bounds() {
+            return None;
+        }
+        self.squares[pos.x as usize][pos.y as usize].take()
+    }
+
+    // Moves whatever is on `from` to `to`, returning the piece that was sitting on `to`.
+    // Dropping a piece back onto its own square leaves the board as it was.
+    pub fn move_piece(&mut self, from: Position, to: Position) -> Option<BoardPiece> {
+        if from == to || !to.in_bounds() {
+            return None;
+        }
+        match self.remove(from) {
+            Some(piece) => self.set(to, piece),
+            None => None,
+        }
+    }
+
+    pub fn stones(&self, color: PieceColor) -> u8 {
+        self.stones[color as usize]
+    }
+
+    // Stone counts are capped at MAX_STONES.
+    pub fn set_stones(&mut self, color: PieceColor, stones: u8) {
+        self.stones[color as usize] = stones.min(MAX_STONES);
+    }
+
+    pub fn army(&self, color: PieceColor) -> ArmyStates {
+        self.armies[color as usize]
+    }
+
+    // Pieces remember the army they were drawn with, so switching a side's army doesn't touch the board.
+    pub fn set_army(&mut self, color: PieceColor, army: ArmyStates) {
+        self.armies[color as usize] = army;
+    }
+
+    pub fn castling_rook(&self, color: PieceColor, side: CastleSide) -> Option<i32> {
+        self.castling[color as usize][side as usize]
+    }
+
+    pub fn set_castling_rook(&mut self, color: PieceColor, side: CastleSide, file: Option<i32>) {
+        self.castling[color as usize][side as usize] = file;
+    }
+
+    // Gives each side the right to castle with the outermost rook on either side of its king, if the king is on its home rank.
+    // Used whenever a position doesn't say who can castle.
+    pub fn infer_castling(&mut self) {
+        for color in [PieceColor::White, PieceColor::Black] {
+            let rank = color.home_rank();
+            let is_own = |board: &Self, x: i32, kind: PieceCursor| {
+                board.get(Position::new(x, rank)).is_some_and(|piece| piece.kind == kind && piece.color == color)
+            };
+            let king = (0..8).find(|x| is_own(self, *x, PieceCursor::King));
+            let (short, long) = match king {
+                Some(king) => (
+                    (king + 1..8).rev().find(|x| is_own(self, *x, PieceCursor::Rook)),
+                    (0..king).find(|x| is_own(self, *x, PieceCursor::Rook)),
+                ),
+                None => (None, None),
+            };
+            self.set_castling_rook(color, CastleSide::Short, short);
+            self.set_castling_rook(color, CastleSide::Long, long);
+        }
+    }
+
+    // Where a side's kings are. Usually just the one, but the Two Kings army has its Warrior King as well.
+    pub fn kings(&self, color: PieceColor) -> Vec<Position> {
+        self.pieces()
+            .filter(|(_, piece)| piece.color == color && piece.is_royal())
+            .map(|(pos, _)| pos)
+            .collect()
+    }
+
+    // Every occupied square, file by file.
+    pub fn pieces(&self) -> impl Iterator<Item = (Position, BoardPiece)> + '_ {
+        (0..8).flat_map(move |x| {
+            (0..8).filter_map(move |y| {
+                let pos = Position::new(x, y);
+                self.get(pos).map(|piece| (pos, piece))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(kind: PieceCursor, color: PieceColor) -> BoardPiece {
+        BoardPiece { kind, color, army: ArmyStates::Classic }
+    }
+
+    #[test]
+    fn squares_off_the_board_are_empty() {
+        let mut board = BoardState::default();
+        assert!(Position::new(0, 0).in_bounds() && Position::new(7, 7).in_bounds());
+        for off_the_board in [Position::new(-1, 0), Position::new(0, 8), Position::new(8, 3)] {
+            assert!(!off_the_board.in_bounds());
+            assert_eq!(board.set(off_the_board, piece(PieceCursor::Pawn, PieceColor::White)), None);
+            assert_eq!(board.get(off_the_board), None);
+            assert_eq!(board.remove(off_the_board), None);
+        }
+        assert_eq!(board.pieces().count(), 0);
+    }
+
+    #[test]
+    fn moving_and_taking_pieces() {
+        let mut board = BoardState::default();
+        let (e2, e4, d5) = (Position::new(4, 1), Position::new(4, 3), Position::new(3, 4));
+        assert_eq!(board.set(e2, piece(PieceCursor::Pawn, PieceColor::White)), None);
+        assert_eq!(board.set(d5, piece(PieceCursor::Knight, PieceColor::Black)), None);
+
+        assert_eq!(board.move_piece(e2, e4), None);
+        assert_eq!(board.get(e2), None);
+        assert_eq!(board.get(e4), Some(piece(PieceCursor::Pawn, PieceColor::White)));
+
+        // Taking hands back the piece that was there.
+        assert_eq!(board.move_piece(d5, e4), Some(piece(PieceCursor::Pawn, PieceColor::White)));
+        assert_eq!(board.pieces().collect::<Vec<_>>(), [(e4, piece(PieceCursor::Knight, PieceColor::Black))]);
+
+        // Dropping a piece where it already is, or off the board, changes nothing.
+        assert_eq!(board.move_piece(e4, e4), None);
+        assert_eq!(board.move_piece(e4, Position::new(4, 8)), None);
+        assert_eq!(board.get(e4), Some(piece(PieceCursor::Knight, PieceColor::Black)));
+        // Nor does moving from an empty square.
+        assert_eq!(board.move_piece(e2, e4), None);
+        assert_eq!(board.get(e4), Some(piece(PieceCursor::Knight, PieceColor::Black)));
+    }
+
+    #[test]
+    fn pieces_are_listed_file_by_file() {
+        let mut board = BoardState::default();
+        let squares = [Position::new(0, 0), Position::new(0, 6), Position::new(3, 2), Position::new(7, 7)];
+        for pos in squares.iter().rev() {
+            board.set(*pos, piece(PieceCursor::Rook, PieceColor::Black));
+        }
+        assert_eq!(board.pieces().map(|(pos, _)| pos).collect::<Vec<_>>(), squares);
+        assert_eq!(board.remove(Position::new(3, 2)), Some(piece(PieceCursor::Rook, PieceColor::Black)));
+        assert_eq!(board.pieces().count(), 3);
+    }
+
+
+    #[test]
+    fn changing_a_sides_army_leaves_its_pieces_alone() {
+        let mut board = BoardState::default();
+        assert_eq!(board.army(PieceColor::White), ArmyStates::Classic);
+        board.set(Position::new(4, 0), piece(PieceCursor::King, PieceColor::White));
+        board.set_army(PieceColor::White, ArmyStates::TwoKings);
+        assert_eq!(board.army(PieceColor::White), ArmyStates::TwoKings);
+        assert_eq!(board.army(PieceColor::Black), ArmyStates::Classic);
+        assert_eq!(board.get(Position::new(4, 0)).unwrap().army, ArmyStates::Classic);
+    }
+
+
+    #[test]
+    fn starting_positions() {
+        let board = BoardState::starting_position(ArmyStates::Nemesis, ArmyStates::Animals);
+        assert_eq!(board.pieces().count(), 32);
+        for color in [PieceColor::White, PieceColor::Black] {
+            let pieces: Vec<_> = board.pieces().filter(|(_, piece)| piece.color == color).collect();
+            assert_eq!(pieces.len(), 16);
+            assert_eq!(pieces.iter().filter(|(_, piece)| piece.kind == PieceCursor::Pawn).count(), 8);
+            assert!(pieces.iter().all(|(_, piece)| piece.army == board.army(color)));
+        }
+        assert_eq!(board.army(PieceColor::White), ArmyStates::Nemesis);
+        assert_eq!(board.army(PieceColor::Black), ArmyStates::Animals);
+        assert_eq!(board.get(Position::new(3, 0)).unwrap().kind, PieceCursor::Queen);
+        assert_eq!(board.get(Position::new(3, 7)).unwrap().kind, PieceCursor::Queen);
+        assert_eq!(board.get(Position::new(4, 7)).unwrap().kind, PieceCursor::King);
+        assert_eq!(board.get(Position::new(0, 6)).unwrap().kind, PieceCursor::Pawn);
+        assert_eq!(board.side_to_move, PieceColor::White);
+        assert_eq!(board.stones(PieceColor::White), STARTING_STONES);
+        assert_eq!(board.stones(PieceColor::Black), STARTING_STONES);
+    }
+
+
+    #[test]
+    fn shuffled_back_ranks_are_mirrored() {
+        let mut back_rank = BACK_RANK;
+        back_rank.swap(3, 4);
+        let board = BoardState::with_back_rank(back_rank, ArmyStates::Classic, ArmyStates::Reaper);
+        assert_eq!(board.pieces().count(), 32);
+        for x in 0..8 {
+            assert_eq!(board.get(Position::new(x, 0)).unwrap().kind, back_rank[x as usize]);
+            assert_eq!(board.get(Position::new(x, 7)).unwrap().kind, back_rank[x as usize]);
+        }
+        assert_eq!(board.get(Position::new(3, 7)).unwrap(), BoardPiece { kind: PieceCursor::King, color: PieceColor::Black, army: ArmyStates::Reaper });
+    }
+
+
+    #[test]
+    fn algebraic_squares() {
+        assert_eq!(Position::new(0, 0).to_algebraic(), "a1");
+        assert_eq!(Position::new(4, 3).to_algebraic(), "e4");
+        assert_eq!(Position::new(7, 7).to_algebraic(), "h8");
+        assert_eq!(Position::from_algebraic("e4"), Some(Position::new(4, 3)));
+        assert_eq!(Position::from_algebraic("h8"), Some(Position::new(7, 7)));
+        for off_the_board in ["i1", "a0", "a9", "e", "e44", "", "E4"] {
+            assert_eq!(Position::from_algebraic(off_the_board), None, "{}", off_the_board);
+        }
+    }
+
+    #[test]
+    fn castling_rights_come_from_the_rooks_beside_the_king() {
+        let board = BoardState::starting_position(ArmyStates::Classic, ArmyStates::Classic);
+        assert_eq!(board.castling_rook(PieceColor::White, CastleSide::Short), Some(7));
+        assert_eq!(board.castling_rook(PieceColor::Black, CastleSide::Long), Some(0));
+
+        // Only the outermost rook on each side counts, and none at all once the king has left its home rank.
+        let mut board = BoardState::default();
+        board.set(Position::new(1, 0), piece(PieceCursor::King, PieceColor::White));
+        board.set(Position::new(0, 0), piece(PieceCursor::Rook, PieceColor::White));
+        board.set(Position::new(5, 0), piece(PieceCursor::Rook, PieceColor::White));
+        board.set(Position::new(6, 0), piece(PieceCursor::Rook, PieceColor::White));
+        board.set(Position::new(7, 0), piece(PieceCursor::Rook, PieceColor::Black));
+        board.set(Position::new(4, 6), piece(PieceCursor::King, PieceColor::Black));
+        board.set(Position::new(7, 7), piece(PieceCursor::Rook, PieceColor::Black));
+        board.infer_castling();
+        assert_eq!(board.castling_rook(PieceColor::White, CastleSide::Short), Some(6));
+        assert_eq!(board.castling_rook(PieceColor::White, CastleSide::Long), Some(0));
+        assert_eq!(board.castling_rook(PieceColor::Black, CastleSide::Short), None);
+        assert_eq!(board.castling_rook(PieceColor::Black, CastleSide::Long), None);
+    }
+
+
+    #[test]
+    fn two_kings_have_two_kings() {
+        let board = BoardState::starting_position(ArmyStates::TwoKings, ArmyStates::Classic);
+        assert_eq!(board.kings(PieceColor::White), [Position::new(3, 0), Position::new(4, 0)]);
+        assert_eq!(board.kings(PieceColor::Black), [Position::new(4, 7)]);
+    }
+}
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::path::Path;
+use super::board_state::{ArmyStates, BoardState, PieceColor};
+use super::fen::{fen2_annotations, from_fen2, FenError, STARTING_FEN2};
+use super::pgn::{parse_army_name, Players};
+use super::render::{render_board, RenderError, RenderOptions};
+
+// Drawing diagrams from the command line, without opening a window:
+//
+//     chess2-canvas --render --fen "<FEN2>" --out diagram.png
+//     chess2-canvas --render --fen-file positions.fen2 --white-army Reaper --black-army "Two Kings" --flip --size 512
+//
+// A file can hold one position per line, for drawing a lot of diagrams at once - each one gets its line number
+// added to the name it's saved under (diagram-1.png, diagram-2.png, ...), even if there's only the one,
+// so scripts always know what to look for. Blank lines are skipped.
+// Circles and arrows in a FEN2 comment are drawn too.
+
+pub const RENDER_FLAG: &str = "--render";
+const DEFAULT_OUT: &str = "diagram.png";
+
+/// What to draw and where to save it, read from the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderRequest {
+    // Where the positions come from, as (line number, FEN2) - the line number is only set when reading from a file.
+    pub positions: Vec<(Option<usize>, String)>,
+    // Armies to draw each side's pieces in, instead of the ones in the FEN2.
+    pub white_army: Option<ArmyStates>,
+    pub black_army: Option<ArmyStates>,
+    pub flipped: bool,
+    pub size: u32,
+    pub out: String,
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    MissingValue(String),
+    UnknownFlag(String),
+    BadSize(String),
+    UnknownArmy(String),
+    BothFenAndFile,
+    Read { path: String, error: String },
+    BadFen { line: Option<usize>, error: FenError },
+    Render(RenderError),
+    Save { path: String, error: String },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::MissingValue(flag) => write!(f, "{} needs a value after it", flag),
+            CliError::UnknownFlag(flag) => write!(f, "'{}' isn't something --render understands", flag),
+            CliError::BadSize(s) => write!(f, "'{}' is not a size in pixels (expected e.g. 768, at least 8)", s),
+            CliError::UnknownArmy(s) => write!(f, "'{}' is not an army (expected Classic, Nemesis, Empowered, Reaper, \"Two Kings\" or Animals)", s),
+            CliError::BothFenAndFile => write!(f, "give either --fen or --fen-file, not both"),
+            CliError::Read { path, error } => write!(f, "couldn't read {}: {}", path, error),
+            CliError::BadFen { line: Some(line), error } => write!(f, "line {}: {}", line, error),
+            CliError::BadFen { line: None, error } => write!(f, "{}", error),
+            CliError::Render(e) => write!(f, "{}", e),
+            CliError::Save { path, error } => write!(f, "couldn't save {}: {}", path, error),
+        }
+    }
+}
+
+impl RenderRequest {
+    /// Reads the flags after --render. Without --fen or --fen-file, the standard starting position is drawn.
+    pub fn from_args(args: &[String]) -> Result<Self, CliError> {
+        let mut request = RenderRequest {
+            positions: Vec::new(),
+            white_army: None,
+            black_army: None,
+            flipped: false,
+            size: RenderOptions::default().size,
+            out: DEFAULT_OUT.to_owned(),
+        };
+        let (mut fen, mut fen_file) = (None, None);
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| CliError::MissingValue(flag.clone()));
+            match flag.as_str() {
+                RENDER_FLAG => {}
+                "--fen" => fen = Some(value()?),
+                "--fen-file" => fen_file = Some(value()?),
+                "--white-army" => request.white_army = Some(army(&value()?)?),
+                "--black-army" => request.black_army = Some(army(&value()?)?),
+                "--flip" => request.flipped = true,
+                "--size" => {
+                    let size = value()?;
+                    request.size = size.parse().ok().filter(|size| *size >= 8).ok_or(CliError::BadSize(size))?;
+                }
+                "--out" => request.out = value()?,
+                _ => return Err(CliError::UnknownFlag(flag.clone())),
+            }
+        }
+
+        request.positions = match (fen, fen_file) {
+            (Some(_), Some(_)) => return Err(CliError::BothFenAndFile),
+            (Some(fen), None) => vec![(None, fen)],
+            (None, Some(path)) => {
+                let text = std::fs::read_to_string(&path).map_err(|e| CliError::Read { path, error: e.to_string() })?;
+                fen_lines(&text)
+            }
+            (None, None) => vec![(None, STARTING_FEN2.to_owned())],
+        };
+        Ok(request)
+    }
+
+    // Where each diagram goes - the name given, numbered by line for positions from a file.
+    fn out_path(&self, line: Option<usize>) -> String {
+        match line {
+            Some(line) => {
+                let out = Path::new(&self.out);
+                let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+                let extension = out.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+                out.with_file_name(format!("{}-{}{}", stem, line, extension)).to_string_lossy().into_owned()
+            }
+            None => self.out.clone(),
+        }
+    }
+}
+
+fn army(name: &str) -> Result<ArmyStates, CliError> {
+    parse_army_name(name).ok_or_else(|| CliError::UnknownArmy(name.to_owned()))
+}
+
+fn fen_lines(text: &str) -> Vec<(Option<usize>, String)> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (Some(i + 1), line.trim().to_owned()))
+        .collect()
+}
+
+// Puts a side's pieces into another army, along with the side itself.
+fn change_army(board: &mut BoardState, color: PieceColor, army: ArmyStates) {
+    board.set_army(color, army);
+    let pieces: Vec<_> = board.pieces().filter(|(_, piece)| piece.color == color).collect();
+    for (pos, mut piece) in pieces {
+        piece.army = army;
+        board.set(pos, piece);
+    }
+}
+
+/// Draws every position asked for and saves them, handing back where they went.
+pub fn render(request: &RenderRequest) -> Result<Vec<String>, CliError> {
+    let options = RenderOptions { size: request.size, flipped: request.flipped };
+    let mut saved = Vec::new();
+    for (line, fen) in &request.positions {
+        let mut board = from_fen2(fen).map_err(|error| CliError::BadFen { line: *line, error })?;
+        if let Some(army) = request.white_army {
+            change_army(&mut board, PieceColor::White, army);
+        }
+        if let Some(army) = request.black_army {
+            change_army(&mut board, PieceColor::Black, army);
+        }
+        let image = render_board(&board, &fen2_annotations(fen), options).map_err(CliError::Render)?;
+        let path = request.out_path(*line);
+        image.save(&path).map_err(|e| CliError::Save { path: path.clone(), error: e.to_string() })?;
+        saved.push(path);
+    }
+    Ok(saved)
+}
+
+/// Runs the command line mode and says how it went. Returns false if anything failed.
+pub fn run(args: &[String]) -> bool {
+    match RenderRequest::from_args(args).and_then(|request| render(&request)) {
+        Ok(saved) => {
+            for path in saved {
+                println!("Saved {}", path);
+            }
+            true
+        }
+        Err(e) => {
+            eprintln!("Couldn't draw the diagram: {}", e);
+            false
+        }
+    }
+}
+
+/// The flags the board editor opens with. They're read once in main and handed to the systems as a resource.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartupArgs {
+    // The position to open with, from `--fen "<FEN2>"`.
+    pub fen: Option<String>,
+    // The Fischer random position to open with instead, from `--fischer <number>` - checked once the board's set up.
+    pub fischer: Option<String>,
+    // Players' names for saved games, from `--white "<name>"` and `--black "<name>"`.
+    pub white: Option<String>,
+    pub black: Option<String>,
+}
+
+impl StartupArgs {
+    pub fn from_args(args: &[String]) -> Self {
+        // A flag with nothing after it gets an empty value, so that it's reported rather than ignored.
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .map(|i| args.get(i + 1).cloned().unwrap_or_default())
+        };
+        StartupArgs {
+            fen: value("--fen"),
+            fischer: value("--fischer"),
+            white: value("--white"),
+            black: value("--black"),
+        }
+    }
+
+    /// Who's playing, with "?" for anybody who wasn't named.
+    pub fn players(&self) -> Players {
+        let unnamed = Players::default();
+        Players {
+            white: self.white.clone().unwrap_or(unnamed.white),
+            black: self.black.clone().unwrap_or(unnamed.black),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_state::Position;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn reading_the_command_line() {
+        let request = RenderRequest::from_args(&args("--render --fen 8/8/8/8/8/8/8/8 --black-army animals --flip --size 256 --out d.png")).unwrap();
+        assert_eq!(request.positions, [(None, "8/8/8/8/8/8/8/8".to_owned())]);
+        assert_eq!((request.white_army, request.black_army), (None, Some(ArmyStates::Animals)));
+        assert!(request.flipped);
+        assert_eq!(request.size, 256);
+        assert_eq!(request.out_path(None), "d.png");
+
+        assert_eq!(RenderRequest::from_args(&args("--render")).unwrap().positions, [(None, STARTING_FEN2.to_owned())]);
+        assert!(matches!(RenderRequest::from_args(&args("--size big")), Err(CliError::BadSize(_))));
+        assert!(matches!(RenderRequest::from_args(&args("--white-army Pirates")), Err(CliError::UnknownArmy(_))));
+        assert!(matches!(RenderRequest::from_args(&args("--out")), Err(CliError::MissingValue(_))));
+        assert!(matches!(RenderRequest::from_args(&args("--fen a --fen-file b")), Err(CliError::BothFenAndFile)));
+        assert!(matches!(RenderRequest::from_args(&args("--colour red")), Err(CliError::UnknownFlag(_))));
+    }
+
+    #[test]
+    fn one_diagram_per_line() {
+        let mut request = RenderRequest::from_args(&args("--out diagrams/d.png")).unwrap();
+        request.positions = fen_lines("8/8/8/8/8/8/8/8\n\n  4k3/8/8/8/8/8/8/4K3 w  \n");
+        assert_eq!(request.positions[1], (Some(3), "4k3/8/8/8/8/8/8/4K3 w".to_owned()));
+        assert_eq!(request.out_path(Some(3)), "diagrams/d-3.png");
+        // Even a file with just the one position gets its number.
+        request.positions.pop();
+        assert_eq!(request.out_path(Some(1)), "diagrams/d-1.png");
+        request.out = "diagram".to_owned();
+        assert_eq!(request.out_path(Some(1)), "diagram-1");
+    }
+
+    #[test]
+    fn armies_can_be_swapped() {
+        let mut board = from_fen2(STARTING_FEN2).unwrap();
+        change_army(&mut board, PieceColor::Black, ArmyStates::Reaper);
+        assert_eq!(board.army(PieceColor::Black), ArmyStates::Reaper);
+        assert_eq!(board.get(Position::new(3, 7)).unwrap().army, ArmyStates::Reaper);
+        assert_eq!(board.get(Position::new(3, 0)).unwrap().army, ArmyStates::Classic);
+    }
+
+    #[test]
+    fn reading_the_editor_flags() {
+        let startup = StartupArgs::from_args(&args("--fen 8/8/8/8/8/8/8/8"));
+        assert_eq!(startup.fen.as_deref(), Some("8/8/8/8/8/8/8/8"));
+        assert_eq!(startup.fischer, None);
+        assert_eq!(StartupArgs::from_args(&args("--fischer 518")).fischer.as_deref(), Some("518"));
+        let players = StartupArgs::from_args(&args("--black Bob")).players();
+        assert_eq!((players.white.as_str(), players.black.as_str()), ("?", "Bob"));
+        assert_eq!(StartupArgs::from_args(&args("")), StartupArgs::default());
+        assert_eq!(StartupArgs::from_args(&args("--fen")).fen.as_deref(), Some(""));
+    }
+}
@@ -0,0 +1,442 @@
+use bevy::prelude::*;
+use std::fmt;
+use super::board::{board_size, Flipped, FONT};
+use super::board_state::*;
+use super::history::GameHistory;
+use super::notation::{bluff_suffix, duel_suffix};
+use super::rules::{Move, MoveKind};
+
+// Duels are how Chess 2 fights back against a capture. When a piece that isn't a king takes a piece that isn't a pawn,
+// the side that lost it may challenge. Both players then secretly bid 0, 1 or 2 of their stones, and both pay what they bid.
+// If the challenger bid more, the capturing piece is destroyed too.
+// A challenge backed by no stones at all is a bluff, and the capturing side gets to call it:
+// either they gain a stone, or the challenger loses one.
+pub const MAX_BID: u8 = 2;
+
+// What the capturing side takes when calling a bluff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BluffCall {
+    GainStone,
+    TakeStone,
+}
+
+/// Where the duel after the last capture has got to.
+/// `attacker` is the square the capturing piece ended up on, and `color` is the capturing side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Duel {
+    #[default]
+    Idle,
+    // The capture has happened, and the other side may challenge it.
+    Offered { attacker: Position, color: PieceColor },
+    // Both sides are choosing their bids. Neither is shown until both are in.
+    Bidding { attacker: Position, color: PieceColor, attacker_bid: Option<u8>, defender_bid: Option<u8> },
+    // The challenger bid nothing, and the capturing side is choosing how to call the bluff.
+    CallingBluff { color: PieceColor },
+}
+
+// How a duel played out, once both bids are in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuelOutcome {
+    pub attacker_bid: u8,
+    pub defender_bid: u8,
+    pub attacker_destroyed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuelError {
+    NothingToChallenge,
+    NotBidding,
+    NoBluffToCall,
+    AlreadyBid(PieceColor),
+    BidTooHigh(u8),
+    NotEnoughStones { bid: u8, stones: u8 },
+}
+
+impl fmt::Display for DuelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DuelError::NothingToChallenge => write!(f, "there's no capture to challenge"),
+            DuelError::NotBidding => write!(f, "nobody is bidding right now"),
+            DuelError::NoBluffToCall => write!(f, "there's no bluff to call"),
+            DuelError::AlreadyBid(color) => write!(f, "{} has already bid", color),
+            DuelError::BidTooHigh(bid) => write!(f, "can't bid {} stones, the most is {}", bid, MAX_BID),
+            DuelError::NotEnoughStones { bid, stones } => write!(f, "can't bid {} stones with only {}", bid, stones),
+        }
+    }
+}
+
+impl Duel {
+    /// The duel a capture opens up, if it can be challenged at all: one enemy piece taken, which isn't a pawn,
+    /// by a piece that isn't a king. Kings can't be duelled, so whirlwinds never can be either.
+    pub fn after_capture(mv: &Move, attacker: BoardPiece, captured: &[BoardPiece]) -> Self {
+        let challengeable = match captured {
+            [taken] => taken.kind != PieceCursor::Pawn && taken.color != attacker.color && !attacker.is_royal(),
+            _ => false,
+        };
+        if !challengeable {
+            return Duel::Idle;
+        }
+        // A pouncing Tiger is back on its own square by the time anyone can challenge it.
+        let square = if mv.kind == MoveKind::Pounce { mv.from } else { mv.to };
+        Duel::Offered { attacker: square, color: attacker.color }
+    }
+
+    pub fn challenge(&mut self) -> Result<(), DuelError> {
+        match *self {
+            Duel::Offered { attacker, color } => {
+                *self = Duel::Bidding { attacker, color, attacker_bid: None, defender_bid: None };
+                Ok(())
+            }
+            _ => Err(DuelError::NothingToChallenge),
+        }
+    }
+
+    pub fn decline(&mut self) -> Result<(), DuelError> {
+        match self {
+            Duel::Offered { .. } => {
+                *self = Duel::Idle;
+                Ok(())
+            }
+            _ => Err(DuelError::NothingToChallenge),
+        }
+    }
+
+    /// Puts in one side's secret bid. Once both are in, the duel is settled on the board and its outcome handed back.
+    pub fn bid(&mut self, board: &mut BoardState, bidder: PieceColor, bid: u8) -> Result<Option<DuelOutcome>, DuelError> {
+        let (attacker, color, mut attacker_bid, mut defender_bid) = match *self {
+            Duel::Bidding { attacker, color, attacker_bid, defender_bid } => (attacker, color, attacker_bid, defender_bid),
+            _ => return Err(DuelError::NotBidding),
+        };
+        if bid > MAX_BID {
+            return Err(DuelError::BidTooHigh(bid));
+        }
+        let stones = board.stones(bidder);
+        if bid > stones {
+            return Err(DuelError::NotEnoughStones { bid, stones });
+        }
+
+        let slot = if bidder == color { &mut attacker_bid } else { &mut defender_bid };
+        if slot.is_some() {
+            return Err(DuelError::AlreadyBid(bidder));
+        }
+        *slot = Some(bid);
+
+        let (attacker_bid, defender_bid) = match (attacker_bid, defender_bid) {
+            (Some(attacker_bid), Some(defender_bid)) => (attacker_bid, defender_bid),
+            _ => {
+                *self = Duel::Bidding { attacker, color, attacker_bid, defender_bid };
+                return Ok(None);
+            }
+        };
+
+        // The first bid was checked when it went in, but the stones could have changed since.
+        // If they no longer cover it, that side has to bid again.
+        let defender = color.opposite();
+        for (side, side_bid) in [(color, attacker_bid), (defender, defender_bid)] {
+            let stones = board.stones(side);
+            if side_bid > stones {
+                *self = Duel::Bidding {
+                    attacker,
+                    color,
+                    attacker_bid: (side != color).then_some(attacker_bid),
+                    defender_bid: (side != defender).then_some(defender_bid),
+                };
+                return Err(DuelError::NotEnoughStones { bid: side_bid, stones });
+            }
+        }
+
+        board.set_stones(color, board.stones(color).saturating_sub(attacker_bid));
+        board.set_stones(defender, board.stones(defender).saturating_sub(defender_bid));
+        let attacker_destroyed = defender_bid > attacker_bid;
+        // Only the capturing piece itself is destroyed - never whatever else has ended up on its square.
+        if attacker_destroyed && board.get(attacker).is_some_and(|piece| piece.color == color) {
+            board.remove(attacker);
+        }
+
+        *self = if defender_bid == 0 { Duel::CallingBluff { color } } else { Duel::Idle };
+        Ok(Some(DuelOutcome { attacker_bid, defender_bid, attacker_destroyed }))
+    }
+
+    pub fn call_bluff(&mut self, board: &mut BoardState, call: BluffCall) -> Result<(), DuelError> {
+        let color = match *self {
+            Duel::CallingBluff { color } => color,
+            _ => return Err(DuelError::NoBluffToCall),
+        };
+        match call {
+            BluffCall::GainStone => board.set_stones(color, board.stones(color) + 1),
+            BluffCall::TakeStone => {
+                let defender = color.opposite();
+                board.set_stones(defender, board.stones(defender).saturating_sub(1));
+            }
+        }
+        *self = Duel::Idle;
+        Ok(())
+    }
+}
+
+// Keys for the duel, which nothing else uses:
+// Y/N to challenge a capture or let it go, Q/W/E for White to bid 0/1/2, I/O/P for Black to bid 0/1/2,
+// and G (gain a stone) or T (take one) to call a bluff.
+pub fn duel_input(
+    kbd: Res<Input<KeyCode>>,
+    mut duel: ResMut<Duel>,
+    mut board: ResMut<BoardState>,
+    mut history: ResMut<GameHistory>,
+) {
+    if kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl) {
+        return;
+    }
+
+    let result = match *duel {
+        Duel::Idle => return,
+        Duel::Offered { .. } => {
+            if kbd.just_pressed(KeyCode::Y) {
+                info!("Duel! White bids with Q/W/E, Black with I/O/P (0/1/2 stones)");
+                duel.challenge()
+            } else if kbd.just_pressed(KeyCode::N) {
+                duel.decline()
+            } else {
+                return;
+            }
+        }
+        Duel::Bidding { .. } => {
+            const BIDS: [(KeyCode, PieceColor, u8); 6] = [
+                (KeyCode::Q, PieceColor::White, 0),
+                (KeyCode::W, PieceColor::White, 1),
+                (KeyCode::E, PieceColor::White, 2),
+                (KeyCode::I, PieceColor::Black, 0),
+                (KeyCode::O, PieceColor::Black, 1),
+                (KeyCode::P, PieceColor::Black, 2),
+            ];
+            let (color, bid) = match BIDS.iter().find(|(key, _, _)| kbd.just_pressed(*key)) {
+                Some((_, color, bid)) => (*color, *bid),
+                None => return,
+            };
+            // Bids are secret, so only say that one went in until the duel is settled.
+            match duel.bid(&mut board, color, bid) {
+                Ok(Some(outcome)) => {
+                    history.annotate(&duel_suffix(&outcome), &board);
+                    info!(
+                        "Duel settled: the capturing side bid {}, the challenger {}{}",
+                        outcome.attacker_bid,
+                        outcome.defender_bid,
+                        if outcome.attacker_destroyed { " - the capturing piece is destroyed" } else { "" }
+                    );
+                    Ok(())
+                }
+                Ok(None) => {
+                    info!("{} has bid", color);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Duel::CallingBluff { .. } => {
+            let call = if kbd.just_pressed(KeyCode::G) {
+                BluffCall::GainStone
+            } else if kbd.just_pressed(KeyCode::T) {
+                BluffCall::TakeStone
+            } else {
+                return;
+            };
+            duel.call_bluff(&mut board, call).map(|()| history.annotate(&bluff_suffix(call).to_string(), &board))
+        }
+    };
+
+    if let Err(e) = result {
+        warn!("Can't do that: {}", e);
+    }
+}
+
+// Each side's stone counter, which sits by the midline on their half of the board.
+#[derive(Component)]
+pub struct StoneCounter(PieceColor);
+
+const STONES: Color = Color::rgb(0.06, 0.06, 0.74);
+
+pub fn draw_stone_counters(mut commands: Commands, asset_server: Res<AssetServer>, windows: Res<Windows>) {
+    let window = windows.get_primary().unwrap();
+    for (color, vertical, y) in [
+        (PieceColor::White, VerticalAlign::Top, -4.),
+        (PieceColor::Black, VerticalAlign::Bottom, 4.),
+    ] {
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: 18.,
+                        color: STONES,
+                    },
+                    TextAlignment {
+                        vertical,
+                        horizontal: HorizontalAlign::Left,
+                    },
+                ),
+                transform: Transform {
+                    translation: Vec3::new(-board_size(window) / 2. + 4., y, 3.),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(StoneCounter(color));
+    }
+}
+
+// Rewrite the counters whenever a side's stones or the duel change, and swap them over when the board is flipped.
+pub fn update_stone_counters(
+    board: Res<BoardState>,
+    duel: Res<Duel>,
+    flipped: Res<Flipped>,
+    mut counters: Query<(&StoneCounter, &mut Text, &mut Transform)>,
+) {
+    if !board.is_changed() && !duel.is_changed() && !flipped.is_changed() {
+        return;
+    }
+    for (counter, mut text, mut transform) in counters.iter_mut() {
+        let side = counter.0;
+        let (vertical, y) = if (side == PieceColor::White) != flipped.0 {
+            (VerticalAlign::Top, -4.)
+        } else {
+            (VerticalAlign::Bottom, 4.)
+        };
+        text.alignment.vertical = vertical;
+        transform.translation.y = y;
+
+        // Let each side know when the duel is waiting on them.
+        let prompt = match *duel {
+            Duel::Offered { color, .. } if color != side => " - challenge? (Y/N)",
+            Duel::Bidding { color, attacker_bid, defender_bid, .. } => {
+                let bid = if color == side { attacker_bid } else { defender_bid };
+                match (bid, side) {
+                    (None, PieceColor::White) => " - bid 0/1/2 (Q/W/E)",
+                    (None, PieceColor::Black) => " - bid 0/1/2 (I/O/P)",
+                    _ => "",
+                }
+            }
+            Duel::CallingBluff { color } if color == side => " - call the bluff: gain a stone (G) or take one (T)",
+            _ => "",
+        };
+        text.sections[0].value = format!("{}: {}/{} stones{}", side, board.stones(side), MAX_STONES, prompt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen2;
+    use crate::history::play_move;
+    use crate::rules::legal_moves;
+
+    fn square(name: &str) -> Position {
+        Position::from_algebraic(name).unwrap()
+    }
+
+    // White's rook on a1 takes Black's knight on a8.
+    fn rook_takes_knight(stones: &str) -> (BoardState, Duel) {
+        let mut b = from_fen2(&format!("n6k/8/8/8/8/8/8/R6K w CC {} - -", stones)).unwrap();
+        let mut duel = Duel::default();
+        let mv = legal_moves(&b, square("a1")).into_iter().find(|mv| mv.to == square("a8")).unwrap();
+        play_move(&mut b, &mut duel, &mut GameHistory::default(), &mv);
+        (b, duel)
+    }
+
+    #[test]
+    fn capturing_a_piece_offers_a_duel() {
+        let (_, duel) = rook_takes_knight("3:3");
+        assert_eq!(duel, Duel::Offered { attacker: square("a8"), color: PieceColor::White });
+    }
+
+    #[test]
+    fn pawns_and_kings_cant_be_duelled() {
+        let mut b = from_fen2("7k/8/8/8/8/8/p7/RK6 w").unwrap();
+        let mut duel = Duel::default();
+        let mv = legal_moves(&b, square("a1")).into_iter().find(|mv| mv.to == square("a2")).unwrap();
+        play_move(&mut b, &mut duel, &mut GameHistory::default(), &mv);
+        assert_eq!(duel, Duel::Idle);
+
+        let mut b = from_fen2("7k/8/8/8/8/8/n7/1K6 w").unwrap();
+        let mv = legal_moves(&b, square("b1")).into_iter().find(|mv| mv.to == square("a2")).unwrap();
+        play_move(&mut b, &mut duel, &mut GameHistory::default(), &mv);
+        assert_eq!(duel, Duel::Idle);
+    }
+
+    #[test]
+    fn outbidding_destroys_the_attacker() {
+        let (mut b, mut duel) = rook_takes_knight("3:3");
+        duel.challenge().unwrap();
+        assert_eq!(duel.bid(&mut b, PieceColor::Black, 2), Ok(None));
+        let outcome = duel.bid(&mut b, PieceColor::White, 1).unwrap().unwrap();
+        assert!(outcome.attacker_destroyed);
+        assert!(b.get(square("a8")).is_none());
+        assert_eq!((b.stones(PieceColor::White), b.stones(PieceColor::Black)), (2, 1));
+        assert_eq!(duel, Duel::Idle);
+    }
+
+    #[test]
+    fn matching_the_bid_saves_the_attacker() {
+        let (mut b, mut duel) = rook_takes_knight("3:3");
+        duel.challenge().unwrap();
+        duel.bid(&mut b, PieceColor::White, 1).unwrap();
+        let outcome = duel.bid(&mut b, PieceColor::Black, 1).unwrap().unwrap();
+        assert!(!outcome.attacker_destroyed);
+        assert_eq!(b.get(square("a8")).unwrap().kind, PieceCursor::Rook);
+    }
+
+    #[test]
+    fn bids_are_limited_by_stones() {
+        let (mut b, mut duel) = rook_takes_knight("3:1");
+        duel.challenge().unwrap();
+        assert_eq!(duel.bid(&mut b, PieceColor::White, 3), Err(DuelError::BidTooHigh(3)));
+        assert_eq!(duel.bid(&mut b, PieceColor::Black, 2), Err(DuelError::NotEnoughStones { bid: 2, stones: 1 }));
+        duel.bid(&mut b, PieceColor::White, 0).unwrap();
+        assert_eq!(duel.bid(&mut b, PieceColor::White, 0), Err(DuelError::AlreadyBid(PieceColor::White)));
+    }
+
+    #[test]
+    fn bids_are_checked_again_when_the_duel_is_settled() {
+        let (mut b, mut duel) = rook_takes_knight("3:3");
+        duel.challenge().unwrap();
+        duel.bid(&mut b, PieceColor::White, 2).unwrap();
+        // White's stones are lowered after bidding, so the bid can't be paid any more.
+        b.set_stones(PieceColor::White, 1);
+        assert_eq!(duel.bid(&mut b, PieceColor::Black, 1), Err(DuelError::NotEnoughStones { bid: 2, stones: 1 }));
+        assert_eq!(duel, Duel::Bidding { attacker: square("a8"), color: PieceColor::White, attacker_bid: None, defender_bid: Some(1) });
+        assert_eq!((b.stones(PieceColor::White), b.stones(PieceColor::Black)), (1, 3));
+
+        let outcome = duel.bid(&mut b, PieceColor::White, 1).unwrap().unwrap();
+        assert!(!outcome.attacker_destroyed);
+        assert_eq!((b.stones(PieceColor::White), b.stones(PieceColor::Black)), (0, 2));
+    }
+
+    #[test]
+    fn only_the_capturing_piece_is_destroyed() {
+        let (mut b, mut duel) = rook_takes_knight("3:3");
+        duel.challenge().unwrap();
+        // The rook has gone and a black piece stands on its square.
+        b.set(square("a8"), BoardPiece { kind: PieceCursor::Queen, color: PieceColor::Black, army: ArmyStates::Classic });
+        duel.bid(&mut b, PieceColor::White, 0).unwrap();
+        assert!(duel.bid(&mut b, PieceColor::Black, 1).unwrap().unwrap().attacker_destroyed);
+        assert_eq!(b.get(square("a8")).unwrap().kind, PieceCursor::Queen);
+    }
+
+    #[test]
+    fn calling_a_bluff() {
+        let (mut b, mut duel) = rook_takes_knight("5:3");
+        duel.challenge().unwrap();
+        duel.bid(&mut b, PieceColor::White, 0).unwrap();
+        duel.bid(&mut b, PieceColor::Black, 0).unwrap();
+        assert_eq!(duel, Duel::CallingBluff { color: PieceColor::White });
+        duel.call_bluff(&mut b, BluffCall::GainStone).unwrap();
+        assert_eq!(b.stones(PieceColor::White), 6);
+
+        // Stones never go past 6.
+        let (mut b, mut duel) = rook_takes_knight("6:3");
+        duel.challenge().unwrap();
+        duel.bid(&mut b, PieceColor::White, 0).unwrap();
+        duel.bid(&mut b, PieceColor::Black, 0).unwrap();
+        duel.call_bluff(&mut b, BluffCall::GainStone).unwrap();
+        assert_eq!(b.stones(PieceColor::White), MAX_STONES);
+    }
+}
@@ -0,0 +1,532 @@
+use bevy::prelude::*;
+use std::fmt;
+use std::fs;
+use super::annotations::Annotations;
+use super::board_state::*;
+use super::rules::winner;
+
+// FEN2 is regular FEN's piece placement and side to move, followed by the two things Chess 2 adds to a position:
+// which army each side is playing (White's, then Black's), and how many duel stones each side holds.
+// Castling rights and the en passant square come last, written the same as in regular FEN.
+//
+//     rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w CN 3:3 KQkq -
+//
+// Castling with a rook that isn't in the corner (Fischer random) is written with the rook's file instead, e.g. Bg.
+// Army letters: C(lassic), N(emesis), E(mpowered), R(eaper), T(wo Kings), A(nimals).
+// A piece that doesn't belong to its side's army carries its own army letter in brackets after it, e.g. Q(R) or n(A).
+// Anything in {braces} after the fields is a comment. Exported positions that are already won say so in one,
+// and so does whatever's been drawn on the board (see annotations.rs), e.g.
+//
+//     4k3/8/8/4K3/8/8/8/8 b CC 3:3 - - {[%csl Re5] White wins by midline invasion}
+
+// The regular chess starting position, which is what the board opens with unless told otherwise.
+pub const STARTING_FEN2: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w CC 3:3 KQkq -";
+
+// Ctrl + E saves the position to this file in the working directory, and Ctrl + L reads it back.
+const FEN2_FILE: &str = "position.fen2";
+// Ctrl + E also saves what's drawn on the board to this file, under the FEN2 of the position it was drawn on.
+// Ctrl + L only uses it for that same position, and only if the FEN2 it loads has nothing drawn in its comment.
+const ANNOTATIONS_FILE: &str = "position.annotations";
+
+// Everything that can be wrong with a FEN2 string. Ranks are numbered like on the board, 8 to 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    Empty,
+    TooManyFields(usize),
+    WrongRankCount(usize),
+    BadRankLength { rank: usize, length: usize },
+    UnknownPiece(char),
+    BadSideToMove(String),
+    BadArmies(String),
+    UnknownArmyLetter(char),
+    UnclosedArmyBracket,
+    InvalidStoneCount(String),
+    BadCastling(String),
+    BadEnPassant(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "the FEN2 string is empty"),
+            FenError::TooManyFields(n) => write!(f, "expected at most 6 fields, found {}", n),
+            FenError::WrongRankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::BadRankLength { rank, length } => write!(f, "rank {} covers {} squares instead of 8", rank, length),
+            FenError::UnknownPiece(c) => write!(f, "'{}' is not a piece", c),
+            FenError::BadSideToMove(s) => write!(f, "side to move must be 'w' or 'b', found '{}'", s),
+            FenError::BadArmies(s) => write!(f, "expected two army letters, found '{}'", s),
+            FenError::UnknownArmyLetter(c) => write!(f, "'{}' is not an army (expected one of C, N, E, R, T, A)", c),
+            FenError::UnclosedArmyBracket => write!(f, "a piece's army letter must be written as e.g. Q(N)"),
+            FenError::InvalidStoneCount(s) => write!(f, "'{}' is not a valid stone count (expected e.g. 3:3, at most {} each)", s, MAX_STONES),
+            FenError::BadCastling(s) => write!(f, "'{}' is not a valid set of castling rights", s),
+            FenError::BadEnPassant(s) => write!(f, "'{}' is not a valid en passant square", s),
+        }
+    }
+}
+
+fn piece_char(piece: &BoardPiece) -> char {
+    let letter = match piece.kind {
+        PieceCursor::King => 'k',
+        PieceCursor::Queen => 'q',
+        PieceCursor::Rook => 'r',
+        PieceCursor::Bishop => 'b',
+        PieceCursor::Knight => 'n',
+        PieceCursor::Pawn => 'p',
+    };
+    match piece.color {
+        PieceColor::White => letter.to_ascii_uppercase(),
+        PieceColor::Black => letter,
+    }
+}
+
+fn army_char(army: ArmyStates) -> char {
+    match army {
+        ArmyStates::Classic => 'C',
+        ArmyStates::Nemesis => 'N',
+        ArmyStates::Empowered => 'E',
+        ArmyStates::Reaper => 'R',
+        ArmyStates::TwoKings => 'T',
+        ArmyStates::Animals => 'A',
+    }
+}
+
+fn parse_piece(c: char) -> Result<(PieceCursor, PieceColor), FenError> {
+    let kind = match c.to_ascii_lowercase() {
+        'k' => PieceCursor::King,
+        'q' => PieceCursor::Queen,
+        'r' => PieceCursor::Rook,
+        'b' => PieceCursor::Bishop,
+        'n' => PieceCursor::Knight,
+        'p' => PieceCursor::Pawn,
+        _ => return Err(FenError::UnknownPiece(c)),
+    };
+    let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+    Ok((kind, color))
+}
+
+fn parse_army(c: char) -> Result<ArmyStates, FenError> {
+    match c {
+        'C' => Ok(ArmyStates::Classic),
+        'N' => Ok(ArmyStates::Nemesis),
+        'E' => Ok(ArmyStates::Empowered),
+        'R' => Ok(ArmyStates::Reaper),
+        'T' => Ok(ArmyStates::TwoKings),
+        'A' => Ok(ArmyStates::Animals),
+        _ => Err(FenError::UnknownArmyLetter(c)),
+    }
+}
+
+fn parse_stones(field: &str) -> Result<(u8, u8), FenError> {
+    let invalid = || FenError::InvalidStoneCount(field.to_owned());
+    let (white, black) = field.split_once(':').ok_or_else(invalid)?;
+    let white: u8 = white.parse().map_err(|_| invalid())?;
+    let black: u8 = black.parse().map_err(|_| invalid())?;
+    if white > MAX_STONES || black > MAX_STONES {
+        return Err(invalid());
+    }
+    Ok((white, black))
+}
+
+// K/Q pick the outermost rook on that side of the king, like in regular FEN; a file letter picks the rook on that file.
+fn parse_castling(board: &mut BoardState, field: &str) -> Result<(), FenError> {
+    let mut outermost = board.clone();
+    outermost.infer_castling();
+    for color in [PieceColor::White, PieceColor::Black] {
+        board.set_castling_rook(color, CastleSide::Short, None);
+        board.set_castling_rook(color, CastleSide::Long, None);
+    }
+    if field == "-" {
+        return Ok(());
+    }
+
+    for c in field.chars() {
+        let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+        let (side, file) = match c.to_ascii_lowercase() {
+            'k' => (CastleSide::Short, outermost.castling_rook(color, CastleSide::Short)),
+            'q' => (CastleSide::Long, outermost.castling_rook(color, CastleSide::Long)),
+            'a'..='h' => {
+                let file = c.to_ascii_lowercase() as i32 - 'a' as i32;
+                let king = board.kings(color).into_iter()
+                    .find(|king| king.y == color.home_rank() && board.get(*king).is_some_and(|piece| piece.kind == PieceCursor::King));
+                match king {
+                    Some(king) if file > king.x => (CastleSide::Short, Some(file)),
+                    Some(king) if file < king.x => (CastleSide::Long, Some(file)),
+                    _ => return Err(FenError::BadCastling(field.to_owned())),
+                }
+            }
+            _ => return Err(FenError::BadCastling(field.to_owned())),
+        };
+        if file.is_none() {
+            return Err(FenError::BadCastling(field.to_owned()));
+        }
+        board.set_castling_rook(color, side, file);
+    }
+    Ok(())
+}
+
+fn castling_field(board: &BoardState) -> String {
+    let mut field = String::new();
+    for color in [PieceColor::White, PieceColor::Black] {
+        for (side, corner, letter) in [(CastleSide::Short, 7, 'k'), (CastleSide::Long, 0, 'q')] {
+            if let Some(file) = board.castling_rook(color, side) {
+                let c = if file == corner { letter } else { (b'a' + file as u8) as char };
+                field.push(match color {
+                    PieceColor::White => c.to_ascii_uppercase(),
+                    PieceColor::Black => c,
+                });
+            }
+        }
+    }
+    if field.is_empty() {
+        field.push('-');
+    }
+    field
+}
+
+/// The circles and arrows saved in a FEN2 string's comment, if it has any.
+pub fn fen2_annotations(fen: &str) -> Annotations {
+    match fen.split_once('{') {
+        Some((_, comment)) => Annotations::from_comment(comment),
+        None => Annotations::default(),
+    }
+}
+
+// The annotations file: the position on the first line, then the [%csl ...][%cal ...] commands drawn on it.
+fn annotations_sidecar(board: &BoardState, annotations: &Annotations) -> String {
+    format!("{}\n{}\n", to_fen2(board), annotations.to_commands())
+}
+
+// What the annotations file has drawn on `board` - nothing if it was saved for another position.
+fn sidecar_annotations(sidecar: &str, board: &BoardState) -> Annotations {
+    match sidecar.split_once('\n') {
+        Some((fen, commands)) if from_fen2(fen).as_ref() == Ok(board) => Annotations::from_comment(commands),
+        _ => Annotations::default(),
+    }
+}
+
+/// Reads a FEN2 string into a fresh board.
+/// Only the piece placement is required - the other fields fall back to White to move, Classic armies and 3 stones each,
+/// castling with any rooks still beside their king, and no en passant.
+pub fn from_fen2(fen: &str) -> Result<BoardState, FenError> {
+    let (fen, _comment) = fen.split_once('{').unwrap_or((fen, ""));
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.is_empty() {
+        return Err(FenError::Empty);
+    }
+    if fields.len() > 6 {
+        return Err(FenError::TooManyFields(fields.len()));
+    }
+
+    let side_to_move = match fields.get(1).copied().unwrap_or("w") {
+        "w" => PieceColor::White,
+        "b" => PieceColor::Black,
+        other => return Err(FenError::BadSideToMove(other.to_owned())),
+    };
+
+    let armies = fields.get(2).copied().unwrap_or("CC");
+    let army_letters: Vec<char> = armies.chars().collect();
+    if army_letters.len() != 2 {
+        return Err(FenError::BadArmies(armies.to_owned()));
+    }
+    let (white_army, black_army) = (parse_army(army_letters[0])?, parse_army(army_letters[1])?);
+
+    let (white_stones, black_stones) = match fields.get(3) {
+        Some(field) => parse_stones(field)?,
+        None => (STARTING_STONES, STARTING_STONES),
+    };
+
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::WrongRankCount(ranks.len()));
+    }
+
+    let mut board = BoardState::default();
+    for (i, rank) in ranks.iter().enumerate() {
+        let y = 7 - i as i32;
+        let mut x = 0;
+        let mut chars = rank.chars().peekable();
+        while let Some(c) = chars.next() {
+            if let Some(empty) = c.to_digit(10) {
+                x += empty as i32;
+                continue;
+            }
+            let (kind, color) = parse_piece(c)?;
+            let army = if chars.peek() == Some(&'(') {
+                chars.next();
+                let letter = chars.next().ok_or(FenError::UnclosedArmyBracket)?;
+                if chars.next() != Some(')') {
+                    return Err(FenError::UnclosedArmyBracket);
+                }
+                parse_army(letter)?
+            } else {
+                match color {
+                    PieceColor::White => white_army,
+                    PieceColor::Black => black_army,
+                }
+            };
+            board.set(Position::new(x, y), BoardPiece { kind, color, army });
+            x += 1;
+        }
+        if x != 8 {
+            return Err(FenError::BadRankLength { rank: 8 - i, length: x as usize });
+        }
+    }
+
+    board.side_to_move = side_to_move;
+    board.set_army(PieceColor::White, white_army);
+    board.set_army(PieceColor::Black, black_army);
+    board.set_stones(PieceColor::White, white_stones);
+    board.set_stones(PieceColor::Black, black_stones);
+
+    match fields.get(4) {
+        Some(field) => parse_castling(&mut board, field)?,
+        None => board.infer_castling(),
+    }
+
+    board.en_passant = match fields.get(5).copied().unwrap_or("-") {
+        "-" => None,
+        square => Some(Position::from_algebraic(square).ok_or_else(|| FenError::BadEnPassant(square.to_owned()))?),
+    };
+    Ok(board)
+}
+
+/// Writes the board out as a FEN2 string, starting from the 8th rank like regular FEN.
+/// If the game is over, the result goes in a comment on the end.
+pub fn to_fen2(board: &BoardState) -> String {
+    to_annotated_fen2(board, &Annotations::default())
+}
+
+/// Same as to_fen2(), with the circles and arrows drawn on the board in the comment as well.
+pub fn to_annotated_fen2(board: &BoardState, annotations: &Annotations) -> String {
+    let mut placement = String::new();
+    for y in (0..8).rev() {
+        let mut empty = 0;
+        for x in 0..8 {
+            match board.get(Position::new(x, y)) {
+                Some(piece) => {
+                    if empty > 0 {
+                        placement.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    placement.push(piece_char(&piece));
+                    if piece.army != board.army(piece.color) {
+                        placement.push('(');
+                        placement.push(army_char(piece.army));
+                        placement.push(')');
+                    }
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if y > 0 {
+            placement.push('/');
+        }
+    }
+
+    let mut fen = format!(
+        "{} {} {}{} {}:{} {} {}",
+        placement,
+        match board.side_to_move {
+            PieceColor::White => 'w',
+            PieceColor::Black => 'b',
+        },
+        army_char(board.army(PieceColor::White)),
+        army_char(board.army(PieceColor::Black)),
+        board.stones(PieceColor::White),
+        board.stones(PieceColor::Black),
+        castling_field(board),
+        board.en_passant.map_or("-".to_owned(), |square| square.to_algebraic()),
+    );
+    let mut comment = annotations.to_commands();
+    if let Some((color, victory)) = winner(board) {
+        if !comment.is_empty() {
+            comment.push(' ');
+        }
+        comment.push_str(&format!("{} wins by {}", color, victory));
+    }
+    if !comment.is_empty() {
+        fen.push_str(&format!(" {{{}}}", comment));
+    }
+    fen
+}
+
+// Print the current board as FEN2 on Ctrl + E, and save what's drawn on it to position.annotations.
+pub fn export_fen2(
+    kbd: Res<Input<KeyCode>>,
+    board: Res<BoardState>,
+    annotations: Res<Annotations>,
+) {
+    if (kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl)) && kbd.just_pressed(KeyCode::E) {
+        let fen = to_annotated_fen2(&board, &annotations);
+        info!("FEN2 exported.");
+        println!("{}", fen);
+        for (file, contents) in [(FEN2_FILE, fen), (ANNOTATIONS_FILE, annotations_sidecar(&board, &annotations))] {
+            if let Err(e) = fs::write(file, contents) {
+                error!("Couldn't write {}: {}", file, e);
+            }
+        }
+    }
+}
+
+// Replace the board with the position in position.fen2 on Ctrl + L, along with the circles and arrows drawn on it -
+// either from its comment, or failing that from position.annotations.
+// A bad string leaves the board alone and says what's wrong with it.
+pub fn import_fen2(
+    kbd: Res<Input<KeyCode>>,
+    mut board: ResMut<BoardState>,
+    mut annotations: ResMut<Annotations>,
+) {
+    if (kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl)) && kbd.just_pressed(KeyCode::L) {
+        match fs::read_to_string(FEN2_FILE) {
+            Ok(fen) => match from_fen2(&fen) {
+                Ok(parsed) => {
+                    *annotations = match fen2_annotations(&fen) {
+                        drawn if drawn.is_empty() => fs::read_to_string(ANNOTATIONS_FILE)
+                            .map(|sidecar| sidecar_annotations(&sidecar, &parsed))
+                            .unwrap_or_default(),
+                        drawn => drawn,
+                    };
+                    *board = parsed;
+                    info!("Loaded {}.", FEN2_FILE);
+                }
+                Err(e) => error!("Couldn't load {}: {}", FEN2_FILE, e),
+            },
+            Err(e) => error!("Couldn't read {}: {}", FEN2_FILE, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(kind: PieceCursor, color: PieceColor, army: ArmyStates) -> BoardPiece {
+        BoardPiece { kind, color, army }
+    }
+
+    #[test]
+    fn an_empty_board() {
+        assert_eq!(to_fen2(&BoardState::default()), "8/8/8/8/8/8/8/8 w CC 3:3 - -");
+    }
+
+    #[test]
+    fn pieces_and_armies() {
+        let mut board = BoardState::default();
+        board.set_army(PieceColor::White, ArmyStates::Reaper);
+        board.set_army(PieceColor::Black, ArmyStates::Animals);
+        board.set(Position::new(4, 0), piece(PieceCursor::King, PieceColor::White, ArmyStates::Reaper));
+        board.set(Position::new(0, 1), piece(PieceCursor::Pawn, PieceColor::White, ArmyStates::Reaper));
+        board.set(Position::new(7, 1), piece(PieceCursor::Pawn, PieceColor::White, ArmyStates::Reaper));
+        board.set(Position::new(3, 4), piece(PieceCursor::Queen, PieceColor::Black, ArmyStates::Nemesis));
+        board.set(Position::new(6, 7), piece(PieceCursor::King, PieceColor::Black, ArmyStates::Animals));
+        board.side_to_move = PieceColor::Black;
+        // The queen isn't from Black's army, so it says which one it is.
+        assert_eq!(to_fen2(&board), "6k1/8/8/3q(N)4/8/8/P6P/4K3 b RA 3:3 - -");
+    }
+
+    #[test]
+    fn starting_position_round_trips() {
+        let board = from_fen2(STARTING_FEN2).unwrap();
+        assert_eq!(board.pieces().count(), 32);
+        assert_eq!(board.get(Position::new(3, 0)), Some(piece(PieceCursor::Queen, PieceColor::White, ArmyStates::Classic)));
+        assert_eq!(to_fen2(&board), STARTING_FEN2);
+
+        let fen = "6k1/8/8/3q(N)4/8/8/P6P/4K3 b RA 0:6 - -";
+        assert_eq!(to_fen2(&from_fen2(fen).unwrap()), fen);
+    }
+
+    #[test]
+    fn only_the_placement_is_needed() {
+        let board = from_fen2("r3k2r/8/8/8/8/8/8/R3K3").unwrap();
+        assert_eq!(board.side_to_move, PieceColor::White);
+        assert_eq!(board.army(PieceColor::White), ArmyStates::Classic);
+        assert_eq!(board.army(PieceColor::Black), ArmyStates::Classic);
+        assert_eq!(board.stones(PieceColor::White), STARTING_STONES);
+        assert_eq!(board.stones(PieceColor::Black), STARTING_STONES);
+        assert_eq!(board.en_passant, None);
+        // Castling is allowed with every rook still beside its king.
+        assert_eq!(to_fen2(&board), "r3k2r/8/8/8/8/8/8/R3K3 w CC 3:3 Qkq -");
+    }
+
+    #[test]
+    fn errors_say_what_went_wrong() {
+        let error = from_fen2("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPP/RNBQKBNR").unwrap_err();
+        assert_eq!(error, FenError::BadRankLength { rank: 2, length: 7 });
+        assert_eq!(error.to_string(), "rank 2 covers 7 squares instead of 8");
+        assert_eq!(from_fen2("8/8/8/8/8/8/8/9").unwrap_err(), FenError::BadRankLength { rank: 1, length: 9 });
+
+        let error = from_fen2("8/8/8/8/8/8/8/8 w CX").unwrap_err();
+        assert_eq!(error, FenError::UnknownArmyLetter('X'));
+        assert_eq!(error.to_string(), "'X' is not an army (expected one of C, N, E, R, T, A)");
+
+        let error = from_fen2("8/8/8/8/8/8/8/8 w CC 7:3").unwrap_err();
+        assert_eq!(error, FenError::InvalidStoneCount("7:3".to_owned()));
+        assert_eq!(error.to_string(), "'7:3' is not a valid stone count (expected e.g. 3:3, at most 6 each)");
+        assert_eq!(from_fen2("8/8/8/8/8/8/8/8 w CC 3").unwrap_err(), FenError::InvalidStoneCount("3".to_owned()));
+
+        assert_eq!(from_fen2("").unwrap_err(), FenError::Empty);
+        assert_eq!(from_fen2("8/8/8/8/8/8/8").unwrap_err(), FenError::WrongRankCount(7));
+        assert_eq!(from_fen2("8/8/8/8/8/8/8/7x").unwrap_err(), FenError::UnknownPiece('x'));
+        assert_eq!(from_fen2("8/8/8/8/8/8/8/8 x").unwrap_err(), FenError::BadSideToMove("x".to_owned()));
+        assert_eq!(from_fen2("8/8/8/8/8/8/8/8 w C").unwrap_err(), FenError::BadArmies("C".to_owned()));
+        assert_eq!(from_fen2("8/8/8/8/8/8/8/8 w CC 3:3 - - x").unwrap_err(), FenError::TooManyFields(7));
+    }
+
+
+    #[test]
+    fn pieces_from_other_armies() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ(R)KBNR w CA 3:3 KQkq -";
+        let board = from_fen2(fen).unwrap();
+        assert_eq!(board.army(PieceColor::White), ArmyStates::Classic);
+        assert_eq!(board.army(PieceColor::Black), ArmyStates::Animals);
+        assert_eq!(board.get(Position::new(3, 0)).unwrap().army, ArmyStates::Reaper);
+        assert_eq!(board.get(Position::new(4, 0)).unwrap().army, ArmyStates::Classic);
+        assert_eq!(board.get(Position::new(3, 7)).unwrap().army, ArmyStates::Animals);
+        assert_eq!(to_fen2(&board), fen);
+
+        let error = from_fen2("7Q(R/8/8/8/8/8/8/8").unwrap_err();
+        assert_eq!(error, FenError::UnclosedArmyBracket);
+        assert_eq!(error.to_string(), "a piece's army letter must be written as e.g. Q(N)");
+        assert_eq!(from_fen2("7Q(/8/8/8/8/8/8/8").unwrap_err(), FenError::UnclosedArmyBracket);
+        assert_eq!(from_fen2("7Q(X)/8/8/8/8/8/8/8").unwrap_err(), FenError::UnknownArmyLetter('X'));
+    }
+
+
+    #[test]
+    fn fischer_castling_and_en_passant() {
+        // White's king is on f1, so castling with the rook on g1 is written with its file.
+        let fen = "r4kr1/pppppppp/8/8/8/8/PPPPPPPP/R4KR1 w CC 3:3 Gq -";
+        let board = from_fen2(fen).unwrap();
+        assert_eq!(board.castling_rook(PieceColor::White, CastleSide::Short), Some(6));
+        assert_eq!(board.castling_rook(PieceColor::White, CastleSide::Long), None);
+        assert_eq!(board.castling_rook(PieceColor::Black, CastleSide::Long), Some(0));
+        assert_eq!(to_fen2(&board), fen);
+        // The file of the king itself isn't a rook to castle with.
+        let error = from_fen2("r4kr1/8/8/8/8/8/8/R4KR1 w CC 3:3 F -").unwrap_err();
+        assert_eq!(error, FenError::BadCastling("F".to_owned()));
+        assert_eq!(error.to_string(), "'F' is not a valid set of castling rights");
+
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w CC 3:3 KQkq e6";
+        let board = from_fen2(fen).unwrap();
+        assert_eq!(board.en_passant, Position::from_algebraic("e6"));
+        assert_eq!(to_fen2(&board), fen);
+        assert_eq!(from_fen2("8/8/8/8/8/8/8/8 w CC 3:3 - e9").unwrap_err(), FenError::BadEnPassant("e9".to_owned()));
+    }
+
+    #[test]
+    fn annotations_file_belongs_to_one_position() {
+        let board = from_fen2(STARTING_FEN2).unwrap();
+        let annotations = Annotations::from_comment("[%csl Gd4][%cal Ge2e4]");
+        let sidecar = annotations_sidecar(&board, &annotations);
+        assert_eq!(sidecar, format!("{}\n[%csl Gd4][%cal Ge2e4]\n", STARTING_FEN2));
+        assert_eq!(sidecar_annotations(&sidecar, &board), annotations);
+
+        let other = from_fen2("4k3/8/8/8/8/8/8/4K3 w").unwrap();
+        assert!(sidecar_annotations(&sidecar, &other).is_empty());
+        // Files saved before the position went in with them are ignored too.
+        assert!(sidecar_annotations("[%csl Gd4]", &board).is_empty());
+    }
+}
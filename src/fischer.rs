@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::board_state::*;
+use super::fen::to_fen2;
+
+// Fischer random setups are picked "by value": every legal back rank has a number from 0 to 959,
+// so two players only need to agree on the number to set up the same position.
+// The numbering is the usual Scharnagl one, where #518 is the regular chess setup.
+pub const FISCHER_POSITIONS: u16 = 960;
+
+// Where the two knights go among the five squares left over once the bishops and queen are down.
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1), (0, 2), (0, 3), (0, 4),
+    (1, 2), (1, 3), (1, 4),
+    (2, 3), (2, 4),
+    (3, 4),
+];
+
+/// The back rank for Fischer random position `id`.
+/// The bishops always end up on opposite colours, and the king always ends up between the rooks.
+pub fn back_rank(id: u16) -> [PieceCursor; 8] {
+    let mut rank: [Option<PieceCursor>; 8] = [None; 8];
+    let n = (id % FISCHER_POSITIONS) as usize;
+
+    // Light-squared bishop on b, d, f or h; dark-squared bishop on a, c, e or g.
+    rank[(n % 4) * 2 + 1] = Some(PieceCursor::Bishop);
+    let n = n / 4;
+    rank[(n % 4) * 2] = Some(PieceCursor::Bishop);
+    let n = n / 4;
+
+    let empty = |rank: &[Option<PieceCursor>; 8]| -> Vec<usize> {
+        (0..8).filter(|x| rank[*x].is_none()).collect()
+    };
+
+    let queen = empty(&rank)[n % 6];
+    rank[queen] = Some(PieceCursor::Queen);
+    let n = n / 6;
+
+    let (first, second) = KNIGHTS[n];
+    let squares = empty(&rank);
+    rank[squares[first]] = Some(PieceCursor::Knight);
+    rank[squares[second]] = Some(PieceCursor::Knight);
+
+    // Rook, king, rook in the three squares that are left.
+    for (x, kind) in empty(&rank).into_iter().zip([PieceCursor::Rook, PieceCursor::King, PieceCursor::Rook]) {
+        rank[x] = Some(kind);
+    }
+
+    rank.map(|kind| kind.unwrap())
+}
+
+// No need for anything fancier than the clock to pick a number out of 960.
+pub fn random_id() -> u16 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or(0);
+    (nanos % FISCHER_POSITIONS as u128) as u16
+}
+
+// A Fischer random position's number, if it is one.
+fn parse_fischer_id(id: &str) -> Option<u16> {
+    id.parse().ok().filter(|id| *id < FISCHER_POSITIONS)
+}
+
+// The Fischer random position asked for with `--fischer <number>` on the command line.
+// A number that doesn't make sense is reported rather than quietly swapped for the regular setup,
+// since the whole point is that both players end up with the same position.
+pub fn fischer_arg(id: &str) -> Option<u16> {
+    let parsed = parse_fischer_id(id);
+    if parsed.is_none() {
+        error!(
+            "'{}' is not a Fischer random position - expected a number from 0 to {}. Using the regular setup instead.",
+            id,
+            FISCHER_POSITIONS - 1
+        );
+    }
+    parsed
+}
+
+/// Sets up Fischer random position `id` for the two armies, and prints its number and FEN2 so it can be passed on.
+pub fn setup_fischer_random(board: &mut BoardState, id: u16) {
+    let (white, black) = (board.army(PieceColor::White), board.army(PieceColor::Black));
+    *board = BoardState::with_back_rank(back_rank(id), white, black);
+    info!("Fischer random position #{}", id);
+    println!("{}", to_fen2(board));
+}
+
+// Set up a random Fischer random position on Shift + R.
+pub fn fischer_random_board(
+    kbd: Res<Input<KeyCode>>,
+    btn: Res<Input<MouseButton>>,
+    mut board: ResMut<BoardState>,
+) {
+    if (kbd.pressed(KeyCode::LShift) || kbd.pressed(KeyCode::RShift)) && kbd.just_pressed(KeyCode::R) && !(btn.pressed(MouseButton::Left)) {
+        setup_fischer_random(&mut board, random_id());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use PieceCursor::*;
+
+    #[test]
+    fn position_518_is_the_regular_setup() {
+        assert_eq!(back_rank(518), [Rook, Knight, Bishop, Queen, King, Bishop, Knight, Rook]);
+    }
+
+    #[test]
+    fn every_position_is_legal_and_different() {
+        let mut seen = HashSet::new();
+        for id in 0..FISCHER_POSITIONS {
+            let rank = back_rank(id);
+            let find = |kind| (0..8).filter(|x| rank[*x] == kind).collect::<Vec<_>>();
+            let (bishops, rooks, king) = (find(Bishop), find(Rook), find(King));
+            assert_eq!((bishops.len(), rooks.len(), king.len()), (2, 2, 1), "#{}", id);
+            assert_eq!(find(Queen).len(), 1, "#{}", id);
+            assert_eq!(find(Knight).len(), 2, "#{}", id);
+            assert_ne!(bishops[0] % 2, bishops[1] % 2, "#{} has both bishops on one colour", id);
+            assert!(rooks[0] < king[0] && king[0] < rooks[1], "#{} has the king outside its rooks", id);
+            assert!(seen.insert(rank), "#{} is a repeat", id);
+        }
+        assert_eq!(seen.len(), 960);
+    }
+
+    #[test]
+    fn each_side_keeps_its_army() {
+        let mut board = BoardState::starting_position(ArmyStates::Reaper, ArmyStates::Empowered);
+        setup_fischer_random(&mut board, 0);
+        assert_eq!(board.army(PieceColor::White), ArmyStates::Reaper);
+        assert_eq!(board.army(PieceColor::Black), ArmyStates::Empowered);
+        let white_rank: Vec<_> = (0..8).map(|x| board.get(Position::new(x, 0)).unwrap().kind).collect();
+        assert_eq!(white_rank, back_rank(0));
+    }
+
+    #[test]
+    fn fischer_ids() {
+        assert_eq!(parse_fischer_id("0"), Some(0));
+        assert_eq!(parse_fischer_id("959"), Some(959));
+        assert_eq!(parse_fischer_id("960"), None);
+        assert_eq!(parse_fischer_id("abc"), None);
+        assert_eq!(parse_fischer_id(""), None);
+    }
+}
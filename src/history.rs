@@ -0,0 +1,318 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use super::annotations::Annotations;
+use super::board::PANEL_WIDTH;
+use super::board_state::*;
+use super::duel::Duel;
+use super::notation::to_san;
+use super::rules::{apply_move, legal_moves, Move};
+
+// One played move: how it's written, the board as it stood right after it, and anything drawn on it there.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub san: String,
+    pub color: PieceColor,
+    pub after: BoardState,
+    pub drawing: Annotations,
+}
+
+/// The moves of the game being played, with a snapshot of the board after each one so we can jump around in it.
+#[derive(Debug, Clone, Default)]
+pub struct GameHistory {
+    // The position before the first move.
+    pub start: BoardState,
+    pub start_drawing: Annotations,
+    pub entries: Vec<HistoryEntry>,
+    // How many of the moves are on the board right now - less than all of them after jumping back.
+    pub current: usize,
+}
+
+impl GameHistory {
+    // The board as it should be at the current point in the game.
+    fn position(&self) -> &BoardState {
+        match self.current {
+            0 => &self.start,
+            n => &self.entries[n - 1].after,
+        }
+    }
+
+    /// Adds a move that was just played on `before`, leaving `after`.
+    /// Playing a move after jumping back drops the moves that used to follow,
+    /// and a move from a position the history doesn't know (the board was edited in between) starts a new game.
+    pub fn record(&mut self, before: &BoardState, mv: &Move, after: &BoardState) {
+        if self.position() != before {
+            *self = GameHistory {
+                start: before.clone(),
+                ..default()
+            };
+        }
+        self.entries.truncate(self.current);
+        self.entries.push(HistoryEntry {
+            san: to_san(before, mv),
+            color: before.side_to_move,
+            after: after.clone(),
+            drawing: Annotations::default(),
+        });
+        self.current = self.entries.len();
+    }
+
+    /// Adds a duel's outcome to the move it followed, and updates the snapshot with the stones spent and pieces lost.
+    pub fn annotate(&mut self, suffix: &str, after: &BoardState) {
+        if self.current == 0 {
+            return;
+        }
+        let entry = &mut self.entries[self.current - 1];
+        entry.san.push_str(suffix);
+        entry.after = after.clone();
+    }
+
+    /// The board after `moves` moves, 0 being the start, and remembers that's where we are.
+    pub fn jump_to(&mut self, moves: usize) -> BoardState {
+        self.current = moves.min(self.entries.len());
+        self.position().clone()
+    }
+
+    /// The circles and arrows drawn on the current position.
+    pub fn drawing(&self) -> &Annotations {
+        match self.current {
+            0 => &self.start_drawing,
+            n => &self.entries[n - 1].drawing,
+        }
+    }
+
+    pub fn set_drawing(&mut self, drawing: &Annotations) {
+        match self.current {
+            0 => self.start_drawing = drawing.clone(),
+            n => self.entries[n - 1].drawing = drawing.clone(),
+        }
+    }
+}
+
+// Moves the board to another point in the game. Whatever's drawn goes with the position it was drawn on,
+// and any duel in progress belonged to the position we're leaving.
+fn go_to_move(moves: usize, history: &mut GameHistory, board: &mut BoardState, duel: &mut Duel, annotations: &mut Annotations) {
+    history.set_drawing(annotations);
+    *board = history.jump_to(moves);
+    *annotations = history.drawing().clone();
+    *duel = Duel::Idle;
+}
+
+/// Plays a move through the rules engine, writes it down, and opens a duel over whatever it captured.
+pub fn play_move(board: &mut BoardState, duel: &mut Duel, history: &mut GameHistory, mv: &Move) -> Vec<BoardPiece> {
+    let attacker = match board.get(mv.from) {
+        Some(piece) => piece,
+        None => return Vec::new(),
+    };
+    let before = board.clone();
+    let captured = apply_move(board, mv);
+    history.record(&before, mv, board);
+    *duel = Duel::after_capture(mv, attacker, &captured);
+    if let Duel::Offered { color, .. } = *duel {
+        info!("{} may challenge the capture: Y to duel, N to let it go", color.opposite());
+    }
+    captured
+}
+
+/// Plays a move made on the board in front of the player. Whatever was drawn stays behind with the position
+/// it was drawn on, and the new position starts out clean.
+pub fn play_move_from_board(
+    board: &mut BoardState,
+    duel: &mut Duel,
+    history: &mut GameHistory,
+    annotations: &mut Annotations,
+    mv: &Move,
+) -> Vec<BoardPiece> {
+    if history.position() == board {
+        history.set_drawing(annotations);
+    }
+    let captured = play_move(board, duel, history, mv);
+    if !annotations.is_empty() {
+        *annotations = Annotations::default();
+    }
+    captured
+}
+
+/// A pawn that's been dropped on the last rank in a game, waiting for its player to pick what it becomes.
+/// Holds the move for each piece the army can promote to - empty when there's nothing to pick.
+#[derive(Default)]
+pub struct PendingPromotion(pub Vec<Move>);
+
+// Asks what the pawn should promote to, then plays that move. Cancelling puts the pawn back where it was.
+pub fn promotion_prompt(
+    mut egui_context: ResMut<EguiContext>,
+    mut pending: ResMut<PendingPromotion>,
+    mut board: ResMut<BoardState>,
+    mut duel: ResMut<Duel>,
+    mut history: ResMut<GameHistory>,
+    mut annotations: ResMut<Annotations>,
+) {
+    if pending.0.is_empty() {
+        return;
+    }
+    // The board can have moved on underneath the prompt, e.g. by stepping through the move list.
+    let from = pending.0[0].from;
+    if !pending.0.iter().all(|mv| legal_moves(&board, from).contains(mv)) {
+        pending.0.clear();
+        return;
+    }
+
+    let mut choice = None;
+    let mut cancel = false;
+    egui::Window::new("Promote to")
+        .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for (i, mv) in pending.0.iter().enumerate() {
+                    if ui.button(to_san(&board, mv)).clicked() {
+                        choice = Some(i);
+                    }
+                }
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+
+    if let Some(i) = choice {
+        let mv = pending.0.swap_remove(i);
+        pending.0.clear();
+        play_move_from_board(&mut board, &mut duel, &mut history, &mut annotations, &mv);
+    } else if cancel {
+        pending.0.clear();
+    }
+}
+
+// Whether the mouse is over the move list, so that clicking it doesn't also pick up, draw or delete the piece underneath.
+#[derive(Default)]
+pub struct PointerOverPanel(pub bool);
+
+pub fn pointer_on_board(over_panel: Res<PointerOverPanel>) -> bool {
+    !over_panel.0
+}
+
+// The move list, in a panel beside the board. Clicking a move puts the board back to how it was right after it.
+pub fn history_panel(
+    mut egui_context: ResMut<EguiContext>,
+    mut history: ResMut<GameHistory>,
+    mut board: ResMut<BoardState>,
+    mut duel: ResMut<Duel>,
+    mut annotations: ResMut<Annotations>,
+    mut over_panel: ResMut<PointerOverPanel>,
+) {
+    let ctx = egui_context.ctx_mut();
+    let mut jump = None;
+
+    egui::SidePanel::right("moves")
+        .width_range(PANEL_WIDTH..=PANEL_WIDTH)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.heading("Moves");
+            if ui.selectable_label(history.current == 0, "Start").clicked() {
+                jump = Some(0);
+            }
+            // One row per move number, White's move then Black's.
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("move_list").show(ui, |ui| {
+                    let mut number = 1;
+                    for (i, entry) in history.entries.iter().enumerate() {
+                        if entry.color == PieceColor::White || i == 0 {
+                            ui.label(format!("{}.", number));
+                        }
+                        if entry.color == PieceColor::Black && i == 0 {
+                            ui.label("...");
+                        }
+                        if ui.selectable_label(history.current == i + 1, &entry.san).clicked() {
+                            jump = Some(i + 1);
+                        }
+                        if entry.color == PieceColor::Black {
+                            number += 1;
+                            ui.end_row();
+                        }
+                    }
+                });
+            });
+        });
+
+    over_panel.0 = ctx.is_pointer_over_area() || ctx.wants_pointer_input();
+
+    if let Some(moves) = jump {
+        go_to_move(moves, &mut history, &mut board, &mut duel, &mut annotations);
+    }
+}
+
+// Step back and forth through the game with , and . - handy after loading one.
+pub fn step_through_history(
+    kbd: Res<Input<KeyCode>>,
+    mut history: ResMut<GameHistory>,
+    mut board: ResMut<BoardState>,
+    mut duel: ResMut<Duel>,
+    mut annotations: ResMut<Annotations>,
+) {
+    let moves = if kbd.just_pressed(KeyCode::Comma) {
+        history.current.saturating_sub(1)
+    } else if kbd.just_pressed(KeyCode::Period) {
+        history.current + 1
+    } else {
+        return;
+    };
+    if moves <= history.entries.len() && moves != history.current {
+        go_to_move(moves, &mut history, &mut board, &mut duel, &mut annotations);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::{from_fen2, STARTING_FEN2};
+    use crate::rules::{apply_move, legal_moves};
+
+    fn play(history: &mut GameHistory, board: &mut BoardState, from: &str, to: &str) {
+        let (from, to) = (Position::from_algebraic(from).unwrap(), Position::from_algebraic(to).unwrap());
+        let mv = legal_moves(board, from).into_iter().find(|mv| mv.to == to).unwrap();
+        let before = board.clone();
+        apply_move(board, &mv);
+        history.record(&before, &mv, board);
+    }
+
+    #[test]
+    fn jumping_back_and_playing_on() {
+        let mut history = GameHistory::default();
+        let mut board = from_fen2(STARTING_FEN2).unwrap();
+        play(&mut history, &mut board, "e2", "e4");
+        play(&mut history, &mut board, "e7", "e5");
+        play(&mut history, &mut board, "g1", "f3");
+        assert_eq!(history.entries.len(), 3);
+
+        board = history.jump_to(1);
+        assert_eq!(board.side_to_move, PieceColor::Black);
+        play(&mut history, &mut board, "c7", "c5");
+        let moves: Vec<&str> = history.entries.iter().map(|entry| entry.san.as_str()).collect();
+        assert_eq!(moves, ["e4", "c5"]);
+
+        assert_eq!(history.jump_to(0), from_fen2(STARTING_FEN2).unwrap());
+    }
+
+    #[test]
+    fn editing_the_board_starts_a_new_game() {
+        let mut history = GameHistory::default();
+        let mut board = from_fen2(STARTING_FEN2).unwrap();
+        play(&mut history, &mut board, "e2", "e4");
+        board.remove(Position::from_algebraic("a7").unwrap());
+        play(&mut history, &mut board, "e7", "e5");
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.start.get(Position::from_algebraic("a7").unwrap()), None);
+    }
+
+
+    #[test]
+    fn playing_a_move_leaves_the_drawing_behind() {
+        let mut board = from_fen2(STARTING_FEN2).unwrap();
+        let mut history = GameHistory { start: board.clone(), ..default() };
+        let mut annotations = Annotations::from_comment("[%cal Ge2e4]");
+        let e2 = Position::from_algebraic("e2").unwrap();
+        let mv = legal_moves(&board, e2).into_iter().find(|mv| mv.to == Position::from_algebraic("e4").unwrap()).unwrap();
+        play_move_from_board(&mut board, &mut Duel::Idle, &mut history, &mut annotations, &mv);
+        assert!(annotations.is_empty());
+        assert_eq!(history.start_drawing, Annotations::from_comment("[%cal Ge2e4]"));
+    }
+}
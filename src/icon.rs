@@ -7,7 +7,7 @@ use winit::window::Icon;
 // Use bevy's WindowId struct and the winit library to display a taskbar/favicon.
 pub fn set_icon(window: NonSend<WinitWindows>) {
     let primary = window.get_window(WindowId::primary()).unwrap();
-    let path = Path::new("assets\\icon.png");
+    let path = Path::new("assets/icon.png");
 
     // image - not bevy-native but bevy doesn't support changing the icon yet
     // Image can be loaded with an asset server - the method below is more direct, and simpler.
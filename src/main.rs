@@ -2,19 +2,45 @@ extern crate bevy_svg;
 
 use bevy::prelude::*;
 use bevy_editor_pls::*;
+use bevy_inspector_egui::bevy_egui::EguiPlugin;
+use bevy_prototype_lyon::prelude::ShapePlugin;
 
 
+mod annotations;
 mod board;
+mod board_state;
+mod cli;
+mod duel;
+mod fen;
+mod fischer;
+mod history;
+mod notation;
+mod pgn;
+mod render;
+mod rules;
 mod icon;
 mod screenshot;
+mod undo;
 
 use board::BoardPlugin;
 
 fn main() {
+    // Drawing diagrams from the command line doesn't need a window at all.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == cli::RENDER_FLAG) {
+        if !cli::run(&args) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let startup = cli::StartupArgs::from_args(&args);
     App::new()
+        .insert_resource(startup.players())
+        .insert_resource(startup)
         .insert_resource(Msaa { samples: 4 }) // Anti-aliasing
         .insert_resource(WindowDescriptor {
-            width: 768.,
+            width: 768. + board::PANEL_WIDTH,
             height: 768.,
             title: "Chess 2 Board Editor".to_owned(),
             resizable: false,
@@ -23,6 +49,8 @@ fn main() {
         .add_startup_system(icon::set_icon)
         .add_system(bevy::input::system::exit_on_esc_system) // Exit game on ESC
         .add_plugins(DefaultPlugins)
+        .add_plugin(ShapePlugin) // Shapes for move hints
+        .add_plugin(EguiPlugin) // The move list
         // Debugging stuff
         //.add_plugin(EditorPlugin)
         //.add_plugin(bevy::diagnostic::FrameTimeDiagnosticsPlugin)
@@ -0,0 +1,166 @@
+use super::board_state::*;
+use super::duel::{BluffCall, DuelOutcome};
+use super::rules::{apply_move, in_check, legal_moves, winner, Move, MoveKind, Victory};
+
+// Chess 2 moves are written in regular algebraic notation (SAN), plus a few additions of its own:
+//
+//     Ke4W        a king's whirlwind, written with the square it stands on
+//     ed3         a Nemesis pawn stepping sideways gives the file it came from, like a pawn capture
+//     Bxf5        a Tiger's pounce is written like any other capture - the Tiger knows to spring back
+//     Rxa8/1:2    a capture that was duelled: the capturing side bid 1 stone, the challenger 2
+//     Rxa8/0:0g   a challenge bluffed with no stones, called for a stone gained (g) or taken away (t)
+//
+// Pieces go by the letter of the slot they stand in, same as in FEN2, so a Warrior King is a Q and an Elephant is an R.
+
+fn piece_letter(kind: PieceCursor) -> Option<char> {
+    match kind {
+        PieceCursor::King => Some('K'),
+        PieceCursor::Queen => Some('Q'),
+        PieceCursor::Rook => Some('R'),
+        PieceCursor::Bishop => Some('B'),
+        PieceCursor::Knight => Some('N'),
+        PieceCursor::Pawn => None,
+    }
+}
+
+fn file_char(x: i32) -> char {
+    (b'a' + x as u8) as char
+}
+
+// The other pieces of the same kind that could go to the same square.
+fn rivals(board: &BoardState, mv: &Move, piece: BoardPiece) -> Vec<Position> {
+    board.pieces()
+        .filter(|(pos, other)| *pos != mv.from && other.kind == piece.kind && other.color == piece.color)
+        .filter(|(pos, _)| legal_moves(board, *pos).iter().any(|other| other.to == mv.to && other.kind != MoveKind::Whirlwind))
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+// The file, rank or whole square of the piece moving, if another piece of the same kind could go to the same square.
+fn disambiguation(board: &BoardState, mv: &Move, piece: BoardPiece) -> String {
+    let rivals = rivals(board, mv, piece);
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|pos| pos.x != mv.from.x) {
+        file_char(mv.from.x).to_string()
+    } else if rivals.iter().all(|pos| pos.y != mv.from.y) {
+        (mv.from.y + 1).to_string()
+    } else {
+        mv.from.to_algebraic()
+    }
+}
+
+// Pawns give the file they came from whenever they leave it - when they capture, and when a Nemesis pawn steps sideways -
+// so only another pawn from the same file can be mistaken for this one, and then the whole square is given.
+fn pawn_origin(board: &BoardState, mv: &Move, piece: BoardPiece) -> String {
+    if rivals(board, mv, piece).iter().any(|pos| pos.x == mv.from.x) {
+        mv.from.to_algebraic()
+    } else if mv.from.x != mv.to.x || !mv.captures.is_empty() {
+        file_char(mv.from.x).to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Writes a legal move on `board` in Chess 2 algebraic notation.
+pub fn to_san(board: &BoardState, mv: &Move) -> String {
+    let piece = match board.get(mv.from) {
+        Some(piece) => piece,
+        None => return String::new(),
+    };
+
+    let mut san = match mv.kind {
+        MoveKind::Castle(CastleSide::Short) => "O-O".to_owned(),
+        MoveKind::Castle(CastleSide::Long) => "O-O-O".to_owned(),
+        MoveKind::Whirlwind => format!("{}{}W", piece_letter(piece.kind).unwrap_or('K'), mv.from.to_algebraic()),
+        _ => {
+            let capture = !mv.captures.is_empty();
+            let mut san = String::new();
+            match piece_letter(piece.kind) {
+                Some(letter) => {
+                    san.push(letter);
+                    san.push_str(&disambiguation(board, mv, piece));
+                }
+                None => san.push_str(&pawn_origin(board, mv, piece)),
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&mv.to.to_algebraic());
+            if let Some(letter) = mv.promotion.and_then(piece_letter) {
+                san.push('=');
+                san.push(letter);
+            }
+            san
+        }
+    };
+
+    let mut after = board.clone();
+    apply_move(&mut after, mv);
+    if matches!(winner(&after), Some((_, Victory::Checkmate))) {
+        san.push('#');
+    } else if in_check(&after, after.side_to_move) {
+        san.push('+');
+    }
+    san
+}
+
+// What a duel adds to the move it followed.
+pub fn duel_suffix(outcome: &DuelOutcome) -> String {
+    format!("/{}:{}", outcome.attacker_bid, outcome.defender_bid)
+}
+
+pub fn bluff_suffix(call: BluffCall) -> char {
+    match call {
+        BluffCall::GainStone => 'g',
+        BluffCall::TakeStone => 't',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::{from_fen2, STARTING_FEN2};
+
+    fn san(fen: &str, from: &str, to: &str) -> String {
+        let board = from_fen2(fen).unwrap();
+        let (from, to) = (Position::from_algebraic(from).unwrap(), Position::from_algebraic(to).unwrap());
+        let mv = legal_moves(&board, from).into_iter().find(|mv| mv.to == to).unwrap();
+        to_san(&board, &mv)
+    }
+
+    #[test]
+    fn pieces_and_pawns() {
+        assert_eq!(san(STARTING_FEN2, "e2", "e4"), "e4");
+        assert_eq!(san(STARTING_FEN2, "g1", "f3"), "Nf3");
+        assert_eq!(san("4k3/8/8/3p4/4P3/8/8/4K3 w", "e4", "d5"), "exd5");
+        assert_eq!(san("k7/4P3/8/8/8/8/8/K7 w", "e7", "e8"), "e8=Q+");
+    }
+
+    #[test]
+    fn same_pieces_are_told_apart() {
+        assert_eq!(san("7k/8/8/8/8/8/8/R4RK1 w", "a1", "d1"), "Rad1");
+        assert_eq!(san("7k/R7/8/8/8/8/8/R5K1 w", "a1", "a4"), "R1a4");
+    }
+
+    #[test]
+    fn pawns_that_change_file_say_where_from() {
+        // Both Nemesis pawns can reach d3: one straight ahead, one stepping towards the king on a8.
+        let fen = "k7/8/8/8/8/8/3PP3/7K w NC";
+        assert_eq!(san(fen, "d2", "d3"), "d3");
+        assert_eq!(san(fen, "e2", "d3"), "ed3");
+        assert_eq!(san("k7/8/8/8/8/8/4P3/7K w NC", "e2", "d2"), "ed2");
+        // With the black king behind them, two pawns on the e-file can both go to e3.
+        let fen = "8/8/8/8/4P3/8/4P3/K3k3 w NC";
+        assert_eq!(san(fen, "e2", "e3"), "e2e3");
+        assert_eq!(san(fen, "e4", "e3"), "e4e3");
+    }
+
+    #[test]
+    fn castling_whirlwinds_and_mate() {
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w", "e1", "g1"), "O-O");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w", "e1", "c1"), "O-O-O");
+        assert_eq!(san("k7/8/8/2p5/3K4/8/8/8 w TC", "d4", "d4"), "Kd4W");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/K3R3 w", "e1", "e8"), "Re8#");
+    }
+}
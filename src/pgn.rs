@@ -0,0 +1,449 @@
+use bevy::prelude::*;
+use chrono::prelude::Local;
+use std::fmt;
+use std::fs;
+use super::annotations::Annotations;
+use super::board_state::*;
+use super::duel::{BluffCall, Duel, DuelError};
+use super::fen::{from_fen2, to_fen2, FenError};
+use super::history::{play_move, GameHistory};
+use super::notation::{bluff_suffix, duel_suffix, to_san};
+use super::rules::{legal_moves, winner, Move};
+
+// PGN2 is regular PGN with a few extra headers for Chess 2, and the moves written in Chess 2 notation (see notation.rs):
+//
+//     [Event "Casual game"]
+//     [Site "chess2-canvas"]
+//     [Date "2022.07.01"]
+//     [Round "-"]
+//     [White "Alice"]
+//     [Black "Bob"]
+//     [Result "1-0"]
+//     [WhiteArmy "Two Kings"]
+//     [BlackArmy "Animals"]
+//     [Termination "midline invasion"]
+//
+//     1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Bxc6/1:0g ...
+//
+// The game is assumed to start from the two armies' usual setup, unless there's a [FEN] header with the FEN2 of where it started.
+// Circles and arrows drawn on a position go in a comment after the move that led to it (or before the first move,
+// for the starting position), as [%csl ...] and [%cal ...] commands - see annotations.rs.
+// Anything else in {comments}, and $NAGs, are skipped over when loading.
+
+// Ctrl + P saves the game to this file in the working directory, and Ctrl + O loads it back.
+const PGN2_FILE: &str = "game.pgn2";
+
+const ARMY_NAMES: [(ArmyStates, &str); 6] = [
+    (ArmyStates::Classic, "Classic"),
+    (ArmyStates::Nemesis, "Nemesis"),
+    (ArmyStates::Empowered, "Empowered"),
+    (ArmyStates::Reaper, "Reaper"),
+    (ArmyStates::TwoKings, "Two Kings"),
+    (ArmyStates::Animals, "Animals"),
+];
+
+fn army_name(army: ArmyStates) -> &'static str {
+    ARMY_NAMES.iter().find(|(a, _)| *a == army).map_or("Classic", |(_, name)| name)
+}
+
+pub fn parse_army_name(name: &str) -> Option<ArmyStates> {
+    ARMY_NAMES.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)).map(|(army, _)| *army)
+}
+
+/// Who's playing, for the PGN2 headers. Set with `--white "<name>"` and `--black "<name>"`, or by loading a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Players {
+    pub white: String,
+    pub black: String,
+}
+
+impl Default for Players {
+    fn default() -> Self {
+        Players {
+            white: "?".to_owned(),
+            black: "?".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    BadHeader(String),
+    UnknownArmy(String),
+    BadFen(FenError),
+    IllegalMove { ply: usize, san: String },
+    BadDuel { ply: usize, annotation: String },
+    DuelRejected { ply: usize, error: DuelError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::BadHeader(line) => write!(f, "'{}' is not a header (expected e.g. [White \"Alice\"])", line),
+            PgnError::UnknownArmy(name) => write!(f, "'{}' is not an army", name),
+            PgnError::BadFen(e) => write!(f, "bad FEN header: {}", e),
+            PgnError::IllegalMove { ply, san } => write!(f, "move {} ('{}') isn't legal", ply, san),
+            PgnError::BadDuel { ply, annotation } => write!(f, "move {} has a bad duel annotation '{}'", ply, annotation),
+            PgnError::DuelRejected { ply, error } => write!(f, "the duel after move {} can't have happened: {}", ply, error),
+        }
+    }
+}
+
+// "1-0", "0-1" or "*" - Chess 2 games can't be drawn.
+fn result_tag(board: &BoardState) -> &'static str {
+    match winner(board) {
+        Some((PieceColor::White, _)) => "1-0",
+        Some((PieceColor::Black, _)) => "0-1",
+        None => "*",
+    }
+}
+
+/// Writes the game out as PGN2, dated `date` (as YYYY.MM.DD).
+pub fn to_pgn2(history: &GameHistory, players: &Players, date: &str) -> String {
+    let start = &history.start;
+    let end = history.entries.last().map_or(start, |entry| &entry.after);
+    let (white_army, black_army) = (start.army(PieceColor::White), start.army(PieceColor::Black));
+    let result = result_tag(end);
+
+    let mut headers = vec![
+        ("Event", "Casual game".to_owned()),
+        ("Site", "chess2-canvas".to_owned()),
+        ("Date", date.to_owned()),
+        ("Round", "-".to_owned()),
+        ("White", players.white.clone()),
+        ("Black", players.black.clone()),
+        ("Result", result.to_owned()),
+        ("WhiteArmy", army_name(white_army).to_owned()),
+        ("BlackArmy", army_name(black_army).to_owned()),
+    ];
+    if let Some((_, victory)) = winner(end) {
+        headers.push(("Termination", victory.to_string()));
+    }
+    if *start != BoardState::starting_position(white_army, black_army) {
+        headers.push(("SetUp", "1".to_owned()));
+        headers.push(("FEN", to_fen2(start)));
+    }
+
+    let mut pgn = String::new();
+    for (name, value) in headers {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "'")));
+    }
+    pgn.push('\n');
+
+    let mut movetext = Vec::new();
+    if !history.start_drawing.is_empty() {
+        movetext.push(format!("{{{}}}", history.start_drawing.to_commands()));
+    }
+    let mut number = 1;
+    for (i, entry) in history.entries.iter().enumerate() {
+        match entry.color {
+            PieceColor::White => movetext.push(format!("{}.", number)),
+            PieceColor::Black if i == 0 => movetext.push(format!("{}...", number)),
+            PieceColor::Black => {}
+        }
+        movetext.push(entry.san.clone());
+        if !entry.drawing.is_empty() {
+            movetext.push(format!("{{{}}}", entry.drawing.to_commands()));
+        }
+        if entry.color == PieceColor::Black {
+            number += 1;
+        }
+    }
+    movetext.push(result.to_owned());
+    pgn.push_str(&movetext.join(" "));
+    pgn.push('\n');
+    pgn
+}
+
+// [Name "Value"]
+fn parse_header(line: &str) -> Result<(&str, &str), PgnError> {
+    let bad = || PgnError::BadHeader(line.to_owned());
+    let inner = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')).ok_or_else(bad)?;
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(bad)?;
+    let value = value.trim().strip_prefix('"').and_then(|v| v.strip_suffix('"')).ok_or_else(bad)?;
+    Ok((name, value))
+}
+
+enum Token<'a> {
+    Move(&'a str),
+    Comment(&'a str),
+}
+
+// The moves and {comments}, without move numbers, results or $NAGs.
+fn tokens(movetext: &str) -> Vec<Token<'_>> {
+    fn moves(text: &str) -> impl Iterator<Item = Token<'_>> {
+        text.split_whitespace()
+            .filter(|token| !["1-0", "0-1", "1/2-1/2", "*"].contains(token))
+            .map(|token| token.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start_matches('.'))
+            .filter(|token| !token.is_empty() && !token.starts_with('$'))
+            .map(Token::Move)
+    }
+
+    let mut tokens = Vec::new();
+    let mut rest = movetext;
+    while let Some(start) = rest.find('{') {
+        tokens.extend(moves(&rest[..start]));
+        let (comment, after) = rest[start + 1..].split_once('}').unwrap_or((&rest[start + 1..], ""));
+        tokens.push(Token::Comment(comment));
+        rest = after;
+    }
+    tokens.extend(moves(rest));
+    tokens
+}
+
+fn without_check(san: &str) -> &str {
+    san.trim_end_matches(['+', '#'])
+}
+
+// The move written as `san`, found by writing out every legal move and seeing which one matches.
+// A move that could be more than one is no move at all, rather than a guess that replays a different game.
+fn find_move(board: &BoardState, san: &str) -> Option<Move> {
+    let mut matches = board.pieces()
+        .filter(|(_, piece)| piece.color == board.side_to_move)
+        .flat_map(|(pos, _)| legal_moves(board, pos))
+        .filter(|mv| without_check(&to_san(board, mv)) == without_check(san));
+    match (matches.next(), matches.next()) {
+        (Some(mv), None) => Some(mv),
+        _ => None,
+    }
+}
+
+// Plays out the duel written after a move, e.g. "1:2" or "0:0g".
+fn replay_duel(board: &mut BoardState, duel: &mut Duel, history: &mut GameHistory, annotation: &str, ply: usize) -> Result<(), PgnError> {
+    let bad = || PgnError::BadDuel { ply, annotation: annotation.to_owned() };
+    let rejected = |error| PgnError::DuelRejected { ply, error };
+
+    let (bids, call) = match annotation.strip_suffix('g') {
+        Some(bids) => (bids, Some(BluffCall::GainStone)),
+        None => match annotation.strip_suffix('t') {
+            Some(bids) => (bids, Some(BluffCall::TakeStone)),
+            None => (annotation, None),
+        },
+    };
+    let (attacker_bid, defender_bid) = bids.split_once(':').ok_or_else(bad)?;
+    let attacker_bid: u8 = attacker_bid.parse().map_err(|_| bad())?;
+    let defender_bid: u8 = defender_bid.parse().map_err(|_| bad())?;
+
+    let color = match *duel {
+        Duel::Offered { color, .. } => color,
+        _ => return Err(rejected(DuelError::NothingToChallenge)),
+    };
+    duel.challenge().map_err(rejected)?;
+    duel.bid(board, color, attacker_bid).map_err(rejected)?;
+    if let Some(outcome) = duel.bid(board, color.opposite(), defender_bid).map_err(rejected)? {
+        history.annotate(&duel_suffix(&outcome), board);
+    }
+    match (call, *duel) {
+        (Some(call), Duel::CallingBluff { .. }) => {
+            duel.call_bluff(board, call).map_err(rejected)?;
+            history.annotate(&bluff_suffix(call).to_string(), board);
+        }
+        (None, Duel::Idle) => {}
+        // A bluff call without a bluff, or a bluff that was never called.
+        _ => return Err(bad()),
+    }
+    Ok(())
+}
+
+/// Reads a PGN2 game, replaying every move (and duel) so that the history has the board after each of them.
+pub fn from_pgn2(pgn: &str) -> Result<(Players, GameHistory), PgnError> {
+    let mut players = Players {
+        white: "?".to_owned(),
+        black: "?".to_owned(),
+    };
+    let mut armies = (ArmyStates::Classic, ArmyStates::Classic);
+    let mut fen = None;
+    let mut movetext = String::new();
+
+    for line in pgn.lines().map(str::trim) {
+        if line.starts_with('[') {
+            let (name, value) = parse_header(line)?;
+            match name {
+                "White" => players.white = value.to_owned(),
+                "Black" => players.black = value.to_owned(),
+                "WhiteArmy" => armies.0 = parse_army_name(value).ok_or_else(|| PgnError::UnknownArmy(value.to_owned()))?,
+                "BlackArmy" => armies.1 = parse_army_name(value).ok_or_else(|| PgnError::UnknownArmy(value.to_owned()))?,
+                "FEN" => fen = Some(value.to_owned()),
+                _ => {}
+            }
+        } else {
+            movetext.push_str(line);
+            movetext.push(' ');
+        }
+    }
+
+    let start = match fen {
+        Some(fen) => from_fen2(&fen).map_err(PgnError::BadFen)?,
+        None => BoardState::starting_position(armies.0, armies.1),
+    };
+    let mut board = start.clone();
+    let mut duel = Duel::Idle;
+    let mut history = GameHistory {
+        start,
+        ..default()
+    };
+
+    let mut ply = 0;
+    for token in tokens(&movetext) {
+        let token = match token {
+            Token::Move(token) => token,
+            // Whatever's drawn belongs to the position the comment follows.
+            Token::Comment(comment) => {
+                let drawing = Annotations::from_comment(comment);
+                if !drawing.is_empty() {
+                    history.set_drawing(&drawing);
+                }
+                continue;
+            }
+        };
+        ply += 1;
+        let (san, annotation) = token.split_once('/').map_or((token, None), |(san, annotation)| (san, Some(annotation)));
+        let mv = find_move(&board, san).ok_or_else(|| PgnError::IllegalMove { ply, san: san.to_owned() })?;
+        play_move(&mut board, &mut duel, &mut history, &mv);
+        match annotation {
+            Some(annotation) => replay_duel(&mut board, &mut duel, &mut history, annotation, ply)?,
+            // Nobody challenged, so the capture stands.
+            None => duel = Duel::Idle,
+        }
+    }
+    Ok((players, history))
+}
+
+// Save the game played so far on Ctrl + P, along with what's drawn on the position on the board.
+pub fn export_pgn2(
+    kbd: Res<Input<KeyCode>>,
+    history: Res<GameHistory>,
+    players: Res<Players>,
+    annotations: Res<Annotations>,
+) {
+    if (kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl)) && kbd.just_pressed(KeyCode::P) {
+        let date = Local::now().format("%Y.%m.%d").to_string();
+        let mut history = history.clone();
+        history.set_drawing(&annotations);
+        match fs::write(PGN2_FILE, to_pgn2(&history, &players, &date)) {
+            Ok(()) => info!("Game saved to {}.", PGN2_FILE),
+            Err(e) => error!("Couldn't write {}: {}", PGN2_FILE, e),
+        }
+    }
+}
+
+// Load the game in game.pgn2 on Ctrl + O. The board goes back to the start of it, ready to step through with , and .
+pub fn import_pgn2(
+    kbd: Res<Input<KeyCode>>,
+    mut board: ResMut<BoardState>,
+    mut duel: ResMut<Duel>,
+    mut history: ResMut<GameHistory>,
+    mut players: ResMut<Players>,
+    mut annotations: ResMut<Annotations>,
+) {
+    if (kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl)) && kbd.just_pressed(KeyCode::O) {
+        match fs::read_to_string(PGN2_FILE) {
+            Ok(pgn) => match from_pgn2(&pgn) {
+                Ok((loaded_players, mut loaded)) => {
+                    *board = loaded.jump_to(0);
+                    *annotations = loaded.drawing().clone();
+                    *history = loaded;
+                    *players = loaded_players;
+                    *duel = Duel::Idle;
+                    info!("Loaded {} ({} moves). Step through it with , and .", PGN2_FILE, history.entries.len());
+                }
+                Err(e) => error!("Couldn't load {}: {}", PGN2_FILE, e),
+            },
+            Err(e) => error!("Couldn't read {}: {}", PGN2_FILE, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players() -> Players {
+        Players {
+            white: "Alice".to_owned(),
+            black: "Bob".to_owned(),
+        }
+    }
+
+    fn sans(history: &GameHistory) -> Vec<&str> {
+        history.entries.iter().map(|entry| entry.san.as_str()).collect()
+    }
+
+    #[test]
+    fn games_survive_a_round_trip() {
+        let pgn = "[White \"Alice\"]\n[Black \"Bob\"]\n[WhiteArmy \"Two Kings\"]\n[BlackArmy \"Animals\"]\n\n1. e4 e5 2. Nf3 {a comment} Nc6 *\n";
+        let (loaded_players, history) = from_pgn2(pgn).unwrap();
+        assert_eq!(loaded_players, players());
+        assert_eq!(sans(&history), ["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(history.start.army(PieceColor::Black), ArmyStates::Animals);
+
+        let saved = to_pgn2(&history, &players(), "2022.07.01");
+        assert!(saved.contains("[Date \"2022.07.01\"]\n"));
+        assert!(saved.contains("[WhiteArmy \"Two Kings\"]\n"));
+        assert!(!saved.contains("[FEN"));
+        assert!(saved.ends_with("\n1. e4 e5 2. Nf3 Nc6 *\n"));
+        let (_, reloaded) = from_pgn2(&saved).unwrap();
+        assert_eq!(reloaded.entries.last().unwrap().after, history.entries.last().unwrap().after);
+    }
+
+    #[test]
+    fn duels_are_replayed() {
+        // The bishop takes the knight, Black challenges and outbids it, and both bishops are gone.
+        let pgn = "[FEN \"7k/8/2n5/1B6/8/8/8/4K3 w CC 3:3 - -\"]\n1. Bxc6/1:2 *";
+        let (_, history) = from_pgn2(pgn).unwrap();
+        let after = &history.entries[0].after;
+        assert_eq!(after.get(Position::from_algebraic("c6").unwrap()), None);
+        assert_eq!((after.stones(PieceColor::White), after.stones(PieceColor::Black)), (2, 1));
+        assert_eq!(sans(&history), ["Bxc6/1:2"]);
+
+        let (_, bluffed) = from_pgn2("[FEN \"7k/8/2n5/1B6/8/8/8/4K3 w CC 3:3 - -\"]\n1. Bxc6/0:0t *").unwrap();
+        assert_eq!(bluffed.entries[0].after.stones(PieceColor::Black), 2);
+        assert!(to_pgn2(&bluffed, &players(), "?").contains("[SetUp \"1\"]\n[FEN \"7k/8/2n5/1B6/8/8/8/4K3 w CC 3:3 - -\"]"));
+
+        assert!(matches!(from_pgn2("1. e4/1:1 *"), Err(PgnError::DuelRejected { ply: 1, .. })));
+    }
+
+    #[test]
+    fn drawings_go_with_their_positions() {
+        let pgn = "{[%csl Ge4]} 1. e4 {Best by test [%cal Ge7e5,Rd7d5]} e5 *";
+        let (_, mut history) = from_pgn2(pgn).unwrap();
+        assert_eq!(history.start_drawing, Annotations::from_comment("[%csl Ge4]"));
+        assert_eq!(history.entries[0].drawing.arrows.len(), 2);
+        assert!(history.entries[1].drawing.is_empty());
+
+        let saved = to_pgn2(&history, &players(), "?");
+        assert!(saved.ends_with("\n{[%csl Ge4]} 1. e4 {[%cal Ge7e5,Rd7d5]} e5 *\n"));
+
+        history.jump_to(1);
+        assert_eq!(*history.drawing(), Annotations::from_comment("[%cal Ge7e5,Rd7d5]"));
+    }
+
+    #[test]
+    fn pawns_from_either_file_come_back_as_played() {
+        // Both Nemesis pawns can go to d3, and the game has to remember which one did.
+        let start = from_fen2("k7/8/8/8/8/8/3PP3/7K w NC 3:3 - -").unwrap();
+        let mut board = start.clone();
+        let mut duel = Duel::Idle;
+        let mut history = GameHistory { start: start.clone(), ..default() };
+        let e2 = Position::from_algebraic("e2").unwrap();
+        let mv = legal_moves(&board, e2).into_iter().find(|mv| mv.to == Position::from_algebraic("d3").unwrap()).unwrap();
+        play_move(&mut board, &mut duel, &mut history, &mv);
+
+        let saved = to_pgn2(&history, &players(), "?");
+        assert!(saved.ends_with("\n1. ed3 *\n"));
+        let (_, reloaded) = from_pgn2(&saved).unwrap();
+        assert_eq!(reloaded.entries[0].after, board);
+        assert_eq!(reloaded.entries[0].after.get(Position::from_algebraic("d2").unwrap()).unwrap().kind, PieceCursor::Pawn);
+    }
+
+    #[test]
+    fn results_and_illegal_moves() {
+        let pgn = "[FEN \"4k3/8/8/8/3K4/8/8/8 w CC 3:3 - -\"]\n1. Ke5 *";
+        let saved = to_pgn2(&from_pgn2(pgn).unwrap().1, &players(), "?");
+        assert!(saved.contains("[Result \"1-0\"]\n"));
+        assert!(saved.contains("[Termination \"midline invasion\"]\n"));
+        assert!(saved.ends_with("1. Ke5 1-0\n"));
+
+        assert!(matches!(from_pgn2("1. e4 e4 *"), Err(PgnError::IllegalMove { ply: 2, san }) if san == "e4"));
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt;
+use std::path::PathBuf;
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use bevy::prelude::{Color, Vec2};
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use super::annotations::{Annotations, ARROW_WIDTH, CIRCLE_RADIUS, CIRCLE_WIDTH};
+use super::board::{brush_color, get_piece_filename, screen_square, Flipped, DARK, FILES, FONT, LIGHT, MIDLINE, RANKS};
+use super::board_state::{BoardState, Position};
+
+// Draws the board straight into an image, without going through the window: squares, midline, notation, pieces,
+// then circles and arrows on top, the same way the app lays them out. Screenshots and the command line both use it.
+// Only the board itself is drawn from the BoardState, so there's no cursor, highlight or half-dragged piece in the picture.
+// It's a copy of the app's drawing rather than a capture of it, though, so the stone counters, duel prompts and
+// game-over banner are left out too - and anything that changes how the board looks in board.rs needs changing here as well.
+
+/// How big the picture is, in pixels along each side, and which way up the board is.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    pub size: u32,
+    pub flipped: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self { size: 768, flipped: false }
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    // A piece image or the font couldn't be read.
+    Asset { path: PathBuf, error: String },
+    BadFont(PathBuf),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Asset { path, error } => write!(f, "couldn't read {}: {}", path.display(), error),
+            RenderError::BadFont(path) => write!(f, "{} isn't a font that can be drawn with", path.display()),
+        }
+    }
+}
+
+// Where an asset lives on disk. Bevy looks in the assets folder next to Cargo.toml when run with cargo,
+// and next to the executable otherwise, so this does too.
+fn asset_path(asset: &str) -> PathBuf {
+    let root = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_exe().ok()?.parent().map(PathBuf::from))
+        .unwrap_or_default();
+    root.join("assets").join(asset)
+}
+
+fn read_asset(asset: &str) -> Result<(PathBuf, Vec<u8>), RenderError> {
+    let path = asset_path(asset);
+    match std::fs::read(&path) {
+        Ok(bytes) => Ok((path, bytes)),
+        Err(error) => Err(RenderError::Asset { path, error: error.to_string() }),
+    }
+}
+
+fn rgba(color: Color) -> [f32; 4] {
+    color.as_rgba_f32()
+}
+
+// Paints a colour over a pixel, as much of it as is covered.
+fn blend(image: &mut RgbaImage, x: i32, y: i32, color: Color, coverage: f32) {
+    if x < 0 || y < 0 || x >= image.width() as i32 || y >= image.height() as i32 {
+        return;
+    }
+    let [r, g, b, a] = rgba(color);
+    let alpha = a * coverage.clamp(0., 1.);
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    for (channel, value) in pixel.0.iter_mut().zip([r, g, b]) {
+        *channel = (value * 255. * alpha + *channel as f32 * (1. - alpha)).round() as u8;
+    }
+}
+
+// Fills in a shape, given a test for whether a point is inside it and a box around it.
+// Each pixel is sampled in a 4x4 grid so the edges come out smooth, like the MSAA in the app.
+fn fill_shape(image: &mut RgbaImage, color: Color, min: Vec2, max: Vec2, inside: impl Fn(Vec2) -> bool) {
+    const SAMPLES: i32 = 4;
+    for y in min.y.floor() as i32..=max.y.ceil() as i32 {
+        for x in min.x.floor() as i32..=max.x.ceil() as i32 {
+            let mut hits = 0;
+            for sy in 0..SAMPLES {
+                for sx in 0..SAMPLES {
+                    let sample = Vec2::new(
+                        x as f32 + (sx as f32 + 0.5) / SAMPLES as f32,
+                        y as f32 + (sy as f32 + 0.5) / SAMPLES as f32,
+                    );
+                    if inside(sample) {
+                        hits += 1;
+                    }
+                }
+            }
+            if hits > 0 {
+                blend(image, x, y, color, hits as f32 / (SAMPLES * SAMPLES) as f32);
+            }
+        }
+    }
+}
+
+// How far a point is from a line segment.
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let along = b - a;
+    let t = ((point - a).dot(along) / along.length_squared()).clamp(0., 1.);
+    point.distance(a + along * t)
+}
+
+fn in_triangle(point: Vec2, [a, b, c]: [Vec2; 3]) -> bool {
+    let side = |p: Vec2, q: Vec2| (q - p).perp_dot(point - p);
+    let (ab, bc, ca) = (side(a, b), side(b, c), side(c, a));
+    (ab >= 0. && bc >= 0. && ca >= 0.) || (ab <= 0. && bc <= 0. && ca <= 0.)
+}
+
+// Writes one letter or number, with the bottom left of its line at the given spot - the same alignment as the app's notation.
+fn draw_text(image: &mut RgbaImage, font: &FontVec, text: char, font_size: f32, bottom_left: Vec2, color: Color) {
+    let font = font.as_scaled(PxScale::from(font_size));
+    let baseline = bottom_left.y + font.descent();
+    let glyph = font.scaled_glyph(text);
+    let glyph = ab_glyph::Glyph { position: point(bottom_left.x, baseline), ..glyph };
+    if let Some(outline) = font.outline_glyph(glyph) {
+        let corner = outline.px_bounds().min;
+        outline.draw(|x, y, coverage| {
+            blend(image, corner.x as i32 + x as i32, corner.y as i32 + y as i32, color, coverage);
+        });
+    }
+}
+
+/// Draws the board, its pieces and what's been drawn over it into a fresh image.
+pub fn render_board(board: &BoardState, annotations: &Annotations, options: RenderOptions) -> Result<RgbaImage, RenderError> {
+    let size = options.size as f32;
+    let tile_size = size / 8.;
+    let flipped = Flipped(options.flipped);
+    // The picture counts down from the top, where the app counts up from the bottom.
+    let top_left = |pos: Position| Vec2::new(pos.x as f32 * tile_size, (7 - pos.y) as f32 * tile_size);
+    let centre = |pos: Position| top_left(screen_square(pos, &flipped)) + Vec2::splat(tile_size / 2.);
+
+    let mut image = RgbaImage::new(options.size, options.size);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let pos = Position::new((x as f32 / tile_size) as i32, 7 - (y as f32 / tile_size) as i32);
+        let [r, g, b, _] = rgba(if (pos.x + pos.y + 1) % 2 == 0 { LIGHT } else { DARK });
+        *pixel = Rgba([(r * 255.).round() as u8, (g * 255.).round() as u8, (b * 255.).round() as u8, 255]);
+    }
+
+    // The midline is as tall as it is in the app: 9.5/128 of a square.
+    let midline = tile_size * 9.5 / 128.;
+    fill_shape(&mut image, MIDLINE, Vec2::new(0., (size - midline) / 2.), Vec2::new(size, (size + midline) / 2.), |point| {
+        (point.y - size / 2.).abs() <= midline / 2.
+    });
+
+    // Files along the bottom and ranks down the right, with the font scaled along with the board.
+    let (font_path, font_bytes) = read_asset(FONT)?;
+    let font = FontVec::try_from_vec(font_bytes).map_err(|_| RenderError::BadFont(font_path))?;
+    let font_size = 18. * size / 768.;
+    for i in 0..8 {
+        let file = Position::new(screen_square(Position::new(i, 0), &flipped).x, 0);
+        let color = if (file.x + file.y + 1) % 2 == 0 { DARK } else { LIGHT };
+        let corner = top_left(file) + Vec2::new(tile_size / 2. - tile_size / 2.3, tile_size / 2. + tile_size / 2.3);
+        draw_text(&mut image, &font, FILES.as_bytes()[i as usize] as char, font_size, corner, color);
+
+        let rank = Position::new(7, screen_square(Position::new(0, i), &flipped).y);
+        let color = if (rank.x + rank.y + 1) % 2 == 0 { DARK } else { LIGHT };
+        let corner = top_left(rank) + Vec2::new(tile_size / 2. + tile_size / 3.3, tile_size / 2. - tile_size / 3.3);
+        draw_text(&mut image, &font, RANKS.as_bytes()[i as usize] as char, font_size, corner, color);
+    }
+
+    // Pieces are drawn at the same scale as their sprites, 0.67 of their size at 96 pixels a square.
+    for (pos, piece) in board.pieces() {
+        let (path, bytes) = read_asset(&get_piece_filename(&piece))?;
+        let sprite = image::load_from_memory(&bytes)
+            .map_err(|error| RenderError::Asset { path, error: error.to_string() })?
+            .to_rgba8();
+        let scale = 0.67 * tile_size / 96.;
+        let (width, height) = (
+            ((sprite.width() as f32 * scale).round() as u32).max(1),
+            ((sprite.height() as f32 * scale).round() as u32).max(1),
+        );
+        let sprite = imageops::resize(&sprite, width, height, FilterType::CatmullRom);
+        let centre = centre(pos);
+        let (x, y) = (centre.x - width as f32 / 2., centre.y - height as f32 / 2.);
+        imageops::overlay(&mut image, &sprite, x.round() as i64, y.round() as i64);
+    }
+
+    for arrow in &annotations.arrows {
+        let (points, head) = arrow.outline(centre(arrow.from), centre(arrow.to), tile_size);
+        let half_width = tile_size * ARROW_WIDTH / 2.;
+        let (mut min, mut max) = (head[0], head[0]);
+        for point in points.iter().chain(&head) {
+            min = min.min(*point - half_width);
+            max = max.max(*point + half_width);
+        }
+        fill_shape(&mut image, brush_color(arrow.brush), min, max, |point| {
+            in_triangle(point, head)
+                || points.windows(2).any(|line| distance_to_segment(point, line[0], line[1]) <= half_width)
+        });
+    }
+    for marker in &annotations.markers {
+        let centre = centre(marker.square);
+        let (radius, half_width) = (tile_size * CIRCLE_RADIUS, tile_size * CIRCLE_WIDTH / 2.);
+        let reach = Vec2::splat(radius + half_width);
+        fill_shape(&mut image, brush_color(marker.brush), centre - reach, centre + reach, |point| {
+            (point.distance(centre) - radius).abs() <= half_width
+        });
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::{Brush, MarkedSquare};
+    use crate::board_state::{ArmyStates, BoardPiece, PieceColor, PieceCursor};
+
+    const ARMIES: [ArmyStates; 6] = [
+        ArmyStates::Classic,
+        ArmyStates::Nemesis,
+        ArmyStates::Empowered,
+        ArmyStates::Reaper,
+        ArmyStates::TwoKings,
+        ArmyStates::Animals,
+    ];
+
+    fn pixel(image: &RgbaImage, x: f32, y: f32) -> [u8; 4] {
+        image.get_pixel(x as u32, y as u32).0
+    }
+
+    fn opaque(color: Color) -> [u8; 4] {
+        let [r, g, b, _] = rgba(color);
+        [(r * 255.).round() as u8, (g * 255.).round() as u8, (b * 255.).round() as u8, 255]
+    }
+
+    // The pixels in a box, given in squares from the top left.
+    fn region(image: &RgbaImage, (x0, y0): (f32, f32), (x1, y1): (f32, f32)) -> Vec<[u8; 4]> {
+        let tile = image.width() as f32 / 8.;
+        let mut pixels = Vec::new();
+        for y in (y0 * tile) as u32..(y1 * tile) as u32 {
+            for x in (x0 * tile) as u32..(x1 * tile) as u32 {
+                pixels.push(image.get_pixel(x, y).0);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn renders_the_board_at_any_size() {
+        let board = BoardState::starting_position(ArmyStates::Classic, ArmyStates::Reaper);
+        let image = render_board(&board, &Annotations::default(), RenderOptions { size: 256, flipped: false }).unwrap();
+        assert_eq!(image.dimensions(), (256, 256));
+        let tile = 32.;
+        // e4 and d5 are empty light squares, d4 an empty dark one.
+        let e4 = pixel(&image, 4.5 * tile, 4.5 * tile);
+        assert_eq!(e4, pixel(&image, 3.5 * tile, 3.5 * tile));
+        assert_ne!(e4, pixel(&image, 3.5 * tile, 4.5 * tile));
+        // e2 is as light as e3, but has a pawn standing in the middle of it.
+        assert_ne!(pixel(&image, 4.5 * tile, 6.5 * tile), pixel(&image, 4.5 * tile, 5.5 * tile));
+        // The midline runs across the middle.
+        assert_eq!(pixel(&image, 3. * tile, 4. * tile), [15, 15, 189, 255]);
+    }
+
+    #[test]
+    fn flipping_and_annotations() {
+        let board = BoardState::starting_position(ArmyStates::Classic, ArmyStates::Classic);
+        let mut annotations = Annotations::default();
+        annotations.toggle_marker(MarkedSquare { square: Position::new(4, 3), brush: Brush::Red });
+        let options = RenderOptions { size: 256, flipped: false };
+        let plain = render_board(&board, &Annotations::default(), options).unwrap();
+        let marked = render_board(&board, &annotations, options).unwrap();
+        // The circle goes round the edge of e4, leaving the middle alone.
+        let tile = 32.;
+        let edge = (4.5 * tile + tile * CIRCLE_RADIUS, 4.5 * tile);
+        assert_ne!(pixel(&plain, edge.0, edge.1), pixel(&marked, edge.0, edge.1));
+        assert_eq!(pixel(&plain, 4.5 * tile, 4.5 * tile), pixel(&marked, 4.5 * tile, 4.5 * tile));
+
+        // Flipped, Black's pieces are at the bottom; the start position looks the same apart from the colours.
+        let flipped = render_board(&board, &Annotations::default(), RenderOptions { flipped: true, ..options }).unwrap();
+        assert_ne!(pixel(&plain, 3.5 * tile, 7.5 * tile), pixel(&flipped, 3.5 * tile, 7.5 * tile));
+    }
+
+    #[test]
+    fn flipped_boards_match_the_app() {
+        let mut board = BoardState::default();
+        board.set(Position::new(0, 0), BoardPiece { kind: PieceCursor::Rook, color: PieceColor::White, army: ArmyStates::Classic });
+        let empty = render_board(&BoardState::default(), &Annotations::default(), RenderOptions::default()).unwrap();
+        let plain = render_board(&board, &Annotations::default(), RenderOptions::default()).unwrap();
+        let flipped = render_board(&board, &Annotations::default(), RenderOptions { flipped: true, ..RenderOptions::default() }).unwrap();
+
+        // a1 is in the bottom left corner, or the top right once flipped.
+        let (bottom_left, top_right) = (((0., 7.), (1., 8.)), ((7., 0.), (8., 1.)));
+        let square = |image, (from, to)| region(image, from, to);
+        assert_ne!(square(&plain, bottom_left), square(&empty, bottom_left));
+        assert_eq!(square(&plain, top_right), square(&empty, top_right));
+        assert_ne!(square(&flipped, top_right), square(&empty, top_right));
+
+        // The squares keep their colours: the bottom left one is always dark.
+        assert_eq!(pixel(&flipped, 2., 674.), opaque(DARK));
+        assert_eq!(pixel(&plain, 2., 674.), opaque(DARK));
+    }
+
+    #[test]
+    fn notation_is_written_in_the_other_square_colour() {
+        let board = BoardState::default();
+        for flipped in [false, true] {
+            let image = render_board(&board, &Annotations::default(), RenderOptions { size: 768, flipped }).unwrap();
+            // The file letter in the (dark) bottom left square is light; the rank numbers on the right are the opposite of their squares.
+            let file = region(&image, (0., 7.5), (0.5, 8.));
+            assert!(file.contains(&opaque(LIGHT)), "flipped: {}", flipped);
+            let bottom_rank = region(&image, (7.5, 7.), (8., 7.5));
+            assert!(bottom_rank.contains(&opaque(DARK)), "flipped: {}", flipped);
+            let next_rank = region(&image, (7.5, 6.), (8., 6.5));
+            assert!(next_rank.contains(&opaque(LIGHT)), "flipped: {}", flipped);
+        }
+
+        // Flipped, the letters and numbers run the other way: h and 8 where a and 1 were.
+        let plain = render_board(&board, &Annotations::default(), RenderOptions::default()).unwrap();
+        let flipped = render_board(&board, &Annotations::default(), RenderOptions { flipped: true, ..RenderOptions::default() }).unwrap();
+        assert_ne!(region(&plain, (0., 7.5), (0.5, 8.)), region(&flipped, (0., 7.5), (0.5, 8.)));
+        assert_ne!(region(&plain, (7.5, 7.), (8., 7.5)), region(&flipped, (7.5, 7.), (8., 7.5)));
+    }
+
+    #[test]
+    fn every_army_has_its_pieces() {
+        let empty = render_board(&BoardState::default(), &Annotations::default(), RenderOptions { size: 256, flipped: false }).unwrap();
+        for army in ARMIES {
+            let board = BoardState::starting_position(army, army);
+            let image = render_board(&board, &Annotations::default(), RenderOptions { size: 256, flipped: false }).unwrap();
+            // Every square on both back ranks and both pawn ranks has something drawn on it.
+            for y in [0., 1., 6., 7.] {
+                for x in 0..8 {
+                    let x = x as f32;
+                    assert_ne!(region(&image, (x, y), (x + 1., y + 1.)), region(&empty, (x, y), (x + 1., y + 1.)), "{:?}", army);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::*;
+use super::classic::Classic;
+
+// The Animals army: Wild Horses for knights, Tigers for bishops, Elephants for rooks and the Jungle Queen for the queen.
+pub struct Animals;
+
+// A Wild Horse jumps like a knight, and will trample anything it lands on - even its own side, as long as it isn't one of their kings.
+fn wild_horse_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    for (dx, dy) in KNIGHT_JUMPS {
+        let to = from.offset(dx, dy);
+        if !to.in_bounds() {
+            continue;
+        }
+        match board.get(to) {
+            None => moves.push(Move::new(from, to, MoveKind::Quiet)),
+            Some(target) if is_ghost(target) || (target.color == piece.color && target.is_royal()) => {}
+            Some(_) => moves.push(Move::new_capture(from, to)),
+        }
+    }
+}
+
+// A Tiger moves up to two squares diagonally. It takes by pouncing: the piece it lands on is gone,
+// but the Tiger springs straight back to the square it came from.
+fn tiger_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    slide(board, from, piece, &DIAGONAL, 2, moves);
+    for mv in moves.iter_mut().filter(|mv| mv.from == from && mv.kind == MoveKind::Capture) {
+        mv.kind = MoveKind::Pounce;
+    }
+}
+
+// An Elephant rampages up to three squares in a straight line, taking every piece in its path, friend or foe.
+// Kings and Ghosts stop it in its tracks, though it can still take an enemy king if nothing else is in the way.
+fn elephant_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    for (dx, dy) in ORTHOGONAL {
+        let mut to = from;
+        let mut captures = Vec::new();
+        for _ in 0..3 {
+            to = to.offset(dx, dy);
+            if !to.in_bounds() {
+                break;
+            }
+            let blocked = match board.get(to) {
+                None => false,
+                Some(target) if target.is_royal() => {
+                    if captures.is_empty() && can_capture(piece, target) {
+                        moves.push(Move::new_capture(from, to));
+                    }
+                    true
+                }
+                Some(target) if is_ghost(target) => true,
+                Some(_) => {
+                    captures.push(to);
+                    false
+                }
+            };
+            if blocked {
+                break;
+            }
+            let kind = if captures.is_empty() { MoveKind::Quiet } else { MoveKind::Capture };
+            moves.push(Move {
+                captures: captures.clone(),
+                ..Move::new(from, to, kind)
+            });
+        }
+    }
+}
+
+impl ArmyRules for Animals {
+    fn piece_moves(&self, board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+        match piece.kind {
+            // The Jungle Queen moves as a rook and a knight, but not as a bishop.
+            PieceCursor::Queen => {
+                slide(board, from, piece, &ORTHOGONAL, 7, moves);
+                slide(board, from, piece, &KNIGHT_JUMPS, 1, moves);
+            }
+            PieceCursor::Rook => elephant_moves(board, from, piece, moves),
+            PieceCursor::Bishop => tiger_moves(board, from, piece, moves),
+            PieceCursor::Knight => wild_horse_moves(board, from, piece, moves),
+            _ => Classic.piece_moves(board, from, piece, moves),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen2;
+
+    fn square(name: &str) -> Position {
+        Position::from_algebraic(name).unwrap()
+    }
+
+    fn find_move(board: &BoardState, from: &str, to: &str) -> Option<Move> {
+        legal_moves(board, square(from)).into_iter().find(|mv| mv.to == square(to))
+    }
+
+    #[test]
+    fn wild_horse_tramples_friends_but_not_its_king() {
+        let b = from_fen2("k7/8/8/8/8/1P3p2/3N4/1K6 w AC").unwrap();
+        assert!(find_move(&b, "d2", "b3").is_some_and(|mv| mv.captures == [square("b3")]));
+        assert!(find_move(&b, "d2", "f3").is_some());
+        assert!(find_move(&b, "d2", "b1").is_none());
+        assert_eq!(legal_moves(&b, square("d2")).len(), 5);
+    }
+
+    #[test]
+    fn tiger_moves_two_squares_and_pounces_back() {
+        let mut b = from_fen2("k7/8/8/5p2/8/3B4/8/K7 w AC").unwrap();
+        let moves = legal_moves(&b, square("d3"));
+        // Two squares each way, with the pawn on f5 cutting one diagonal short.
+        assert_eq!(moves.len(), 8);
+        assert!(find_move(&b, "d3", "b5").is_some());
+        assert!(find_move(&b, "d3", "g6").is_none());
+
+        let pounce = find_move(&b, "d3", "f5").unwrap();
+        assert_eq!(pounce.kind, MoveKind::Pounce);
+        apply_move(&mut b, &pounce);
+        assert!(b.get(square("f5")).is_none());
+        assert_eq!(b.get(square("d3")).unwrap().kind, PieceCursor::Bishop);
+    }
+
+    #[test]
+    fn elephant_rampages_through_everything_in_its_path() {
+        let mut b = from_fen2("k7/8/8/8/8/8/8/R1Pp3K w AC").unwrap();
+        let mv = find_move(&b, "a1", "d1").unwrap();
+        assert_eq!(mv.captures, vec![square("c1"), square("d1")]);
+        assert!(find_move(&b, "a1", "e1").is_none());
+        apply_move(&mut b, &mv);
+        assert!(b.get(square("c1")).is_none());
+        assert_eq!(b.get(square("d1")).unwrap().kind, PieceCursor::Rook);
+    }
+
+    #[test]
+    fn elephant_is_stopped_by_kings() {
+        let b = from_fen2("8/8/8/8/k7/8/p7/R6K w AC").unwrap();
+        // It can rampage through the pawn on a2, but not the king behind it on a4.
+        assert!(find_move(&b, "a1", "a3").is_some());
+        assert!(find_move(&b, "a1", "a4").is_none());
+
+        let b = from_fen2("8/8/8/8/8/k7/8/R6K w AC").unwrap();
+        assert!(find_move(&b, "a1", "a3").is_some_and(|mv| mv.captures == [square("a3")]));
+    }
+
+    #[test]
+    fn jungle_queen_moves_as_rook_and_knight() {
+        let b = from_fen2("k7/8/8/8/3Q4/8/8/7K w AC").unwrap();
+        assert!(find_move(&b, "d4", "d8").is_some());
+        assert!(find_move(&b, "d4", "e6").is_some());
+        assert!(find_move(&b, "d4", "e5").is_none());
+        assert_eq!(legal_moves(&b, square("d4")).len(), 14 + 8);
+    }
+}
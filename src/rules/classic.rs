@@ -0,0 +1,233 @@
+use super::*;
+
+// The pieces of regular chess.
+pub struct Classic;
+
+impl ArmyRules for Classic {
+    fn piece_moves(&self, board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+        match piece.kind {
+            PieceCursor::King => slide(board, from, piece, &ALL_DIRECTIONS, 1, moves),
+            PieceCursor::Queen => slide(board, from, piece, &ALL_DIRECTIONS, 7, moves),
+            PieceCursor::Rook => slide(board, from, piece, &ORTHOGONAL, 7, moves),
+            PieceCursor::Bishop => slide(board, from, piece, &DIAGONAL, 7, moves),
+            PieceCursor::Knight => slide(board, from, piece, &KNIGHT_JUMPS, 1, moves),
+            PieceCursor::Pawn => pawn_moves(board, from, piece, moves),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen2;
+
+    fn board(placement: &str) -> BoardState {
+        from_fen2(placement).unwrap()
+    }
+
+    fn square(name: &str) -> Position {
+        Position::from_algebraic(name).unwrap()
+    }
+
+    // Legal destinations as sorted square names, to keep the assertions readable.
+    fn destinations(board: &BoardState, from: &str) -> Vec<String> {
+        let mut squares: Vec<String> = legal_destinations(board, square(from)).iter().map(|pos| pos.to_algebraic()).collect();
+        squares.sort();
+        squares
+    }
+
+    fn find_move(board: &BoardState, from: &str, to: &str) -> Move {
+        legal_moves(board, square(from)).into_iter().find(|mv| mv.to == square(to)).unwrap()
+    }
+
+    #[test]
+    fn king_steps_one_square_but_not_into_check() {
+        let b = board("8/8/8/8/4K3/8/8/k7 w");
+        assert_eq!(destinations(&b, "e4"), ["d3", "d4", "d5", "e3", "e5", "f3", "f4", "f5"]);
+
+        let b = board("3r4/8/8/8/8/8/8/k3K3 w");
+        assert_eq!(destinations(&b, "e1"), ["e2", "f1", "f2"]);
+    }
+
+    #[test]
+    fn queen_moves_like_rook_and_bishop() {
+        let b = board("k7/8/8/8/3Q4/8/8/7K w");
+        assert_eq!(legal_destinations(&b, square("d4")).len(), 27);
+    }
+
+    #[test]
+    fn rook_stops_at_pieces_and_captures_enemies() {
+        let b = board("k7/8/8/3p4/8/8/3R1P2/7K w");
+        assert_eq!(destinations(&b, "d2"), ["a2", "b2", "c2", "d1", "d3", "d4", "d5", "e2"]);
+    }
+
+    #[test]
+    fn bishop_moves_diagonally() {
+        let b = board("k7/8/8/8/8/2b5/8/B6K w");
+        assert_eq!(destinations(&b, "a1"), ["b2", "c3"]);
+    }
+
+    #[test]
+    fn knight_jumps_over_pieces() {
+        let b = board("k7/8/8/8/8/8/PPP5/NP5K w");
+        assert_eq!(destinations(&b, "a1"), ["b3"]);
+        let b = board("k7/8/8/3N4/8/8/8/7K w");
+        assert_eq!(legal_destinations(&b, square("d5")).len(), 8);
+    }
+
+    #[test]
+    fn pawn_steps_double_steps_and_captures() {
+        let b = board("k7/8/8/8/8/3p4/4P3/7K w");
+        assert_eq!(destinations(&b, "e2"), ["d3", "e3", "e4"]);
+
+        let b = board("k7/8/8/8/4n3/8/4P3/7K w");
+        assert_eq!(destinations(&b, "e2"), ["e3"]);
+
+        let b = board("k7/8/8/8/8/8/7p/K7 b");
+        assert_eq!(destinations(&b, "h2"), ["h1"]);
+    }
+
+    #[test]
+    fn en_passant_takes_the_pawn_that_skipped_past() {
+        let mut b = board("k7/8/8/3Pp3/8/8/8/7K w CC 3:3 - e6");
+        assert_eq!(destinations(&b, "d5"), ["d6", "e6"]);
+
+        let mv = find_move(&b, "d5", "e6");
+        assert_eq!(mv.kind, MoveKind::EnPassant);
+        apply_move(&mut b, &mv);
+        assert!(b.get(square("e5")).is_none());
+        assert_eq!(b.get(square("e6")).unwrap().kind, PieceCursor::Pawn);
+    }
+
+    #[test]
+    fn double_step_allows_en_passant() {
+        let mut b = board("k7/8/8/8/3p4/8/4P3/7K w CC 3:3 - -");
+        let mv = find_move(&b, "e2", "e4");
+        apply_move(&mut b, &mv);
+        assert_eq!(b.en_passant, Some(square("e3")));
+        assert_eq!(destinations(&b, "d4"), ["d3", "e3"]);
+    }
+
+    #[test]
+    fn pawns_promote_within_their_army_but_never_to_a_king() {
+        let b = board("k7/4P3/8/8/8/8/8/7K w");
+        let promotions: Vec<PieceCursor> = legal_moves(&b, square("e7")).iter().filter_map(|mv| mv.promotion).collect();
+        assert_eq!(promotions, [PieceCursor::Queen, PieceCursor::Rook, PieceCursor::Bishop, PieceCursor::Knight]);
+
+        let mut b = b;
+        let mv = legal_moves(&b, square("e7")).into_iter().find(|mv| mv.promotion == Some(PieceCursor::Knight)).unwrap();
+        apply_move(&mut b, &mv);
+        let promoted = b.get(square("e8")).unwrap();
+        assert_eq!(promoted.kind, PieceCursor::Knight);
+        assert_eq!(promoted.army, ArmyStates::Classic);
+    }
+
+    #[test]
+    fn castling_both_ways() {
+        let mut b = board("r3k2r/8/8/8/8/8/8/R3K2R w");
+        assert_eq!(destinations(&b, "e1"), ["c1", "d1", "d2", "e2", "f1", "f2", "g1"]);
+
+        let mv = find_move(&b, "e1", "g1");
+        apply_move(&mut b, &mv);
+        assert_eq!(b.get(square("f1")).unwrap().kind, PieceCursor::Rook);
+        assert!(b.get(square("h1")).is_none());
+        assert_eq!(b.castling_rook(PieceColor::White, CastleSide::Long), None);
+
+        let mv = find_move(&b, "e8", "c8");
+        apply_move(&mut b, &mv);
+        assert_eq!(b.get(square("d8")).unwrap().kind, PieceCursor::Rook);
+        assert_eq!(b.get(square("c8")).unwrap().kind, PieceCursor::King);
+    }
+
+    #[test]
+    fn dropping_the_king_on_its_rook_castles() {
+        let drop = |b: &BoardState, from: &str, to: &str| drop_moves(b, square(from), square(to)).iter().map(|mv| mv.kind).collect::<Vec<_>>();
+
+        // The usual way: the square the king castles to is somewhere it couldn't otherwise go.
+        let b = board("r3k2r/8/8/8/8/8/8/R3K2R w");
+        assert_eq!(drop(&b, "e1", "g1"), [MoveKind::Castle(CastleSide::Short)]);
+        assert_eq!(drop(&b, "e1", "h1"), [MoveKind::Castle(CastleSide::Short)]);
+        assert_eq!(drop(&b, "e1", "f1"), [MoveKind::Quiet]);
+
+        // In Fischer random the king on b1 can step to c1 as well as castle there, so c1 is the step and a1 the castle.
+        let b = board("7k/8/8/8/8/8/8/RK6 w CC 3:3 A -");
+        assert_eq!(drop(&b, "b1", "c1"), [MoveKind::Quiet]);
+        assert_eq!(drop(&b, "b1", "a1"), [MoveKind::Castle(CastleSide::Long)]);
+
+        // A king already on g1 castles without moving, which mustn't happen just by putting it back down.
+        let b = board("7k/8/8/8/8/8/8/6KR w CC 3:3 H -");
+        assert!(drop(&b, "g1", "g1").is_empty());
+        assert_eq!(drop(&b, "g1", "h1"), [MoveKind::Castle(CastleSide::Short)]);
+
+        // Promotions are the only time a drop means more than one move.
+        let b = board("k7/4P3/8/8/8/8/8/K7 w");
+        let promotions = drop_moves(&b, square("e7"), square("e8"));
+        assert_eq!(promotions.len(), 4);
+        assert!(promotions.iter().all(|mv| mv.promotion.is_some()));
+    }
+
+    #[test]
+    fn no_castling_when_blocked_in_check_or_through_check() {
+        let b = board("4k3/8/8/8/8/8/8/RN2K2R w");
+        assert!(!destinations(&b, "e1").contains(&"c1".to_owned()));
+        assert!(destinations(&b, "e1").contains(&"g1".to_owned()));
+
+        let b = board("4k3/8/8/8/8/8/8/R3K2R w CC 3:3 - -");
+        assert!(!destinations(&b, "e1").contains(&"g1".to_owned()));
+
+        let b = board("4k3/8/8/8/8/8/8/R3K2R w CC 3:3 Q -");
+        assert!(!destinations(&b, "e1").contains(&"g1".to_owned()));
+        assert!(destinations(&b, "e1").contains(&"c1".to_owned()));
+
+        let b = board("4k3/8/8/8/8/8/4r3/R3K2R w");
+        assert_eq!(destinations(&b, "e1"), ["d1", "e2", "f1"]);
+
+        let b = board("4kr2/8/8/8/8/8/8/R3K2R w");
+        assert!(!destinations(&b, "e1").contains(&"g1".to_owned()));
+        assert!(destinations(&b, "e1").contains(&"c1".to_owned()));
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin() {
+        let b = board("4r2k/8/8/8/8/8/4R3/4K3 w");
+        assert_eq!(destinations(&b, "e2"), ["e3", "e4", "e5", "e6", "e7", "e8"]);
+    }
+
+    #[test]
+    fn moving_the_rook_loses_that_side_only() {
+        let mut b = board("r3k2r/8/8/8/8/8/8/R3K2R w");
+        let mv = find_move(&b, "h1", "h2");
+        apply_move(&mut b, &mv);
+        assert_eq!(b.castling_rook(PieceColor::White, CastleSide::Short), None);
+        assert_eq!(b.castling_rook(PieceColor::White, CastleSide::Long), Some(0));
+        assert_eq!(b.side_to_move, PieceColor::Black);
+    }
+
+    #[test]
+    fn king_crossing_the_midline_wins() {
+        let mut b = board("k7/8/8/8/4K3/8/8/8 w");
+        assert_eq!(winner(&b), None);
+        let mv = find_move(&b, "e4", "e5");
+        apply_move(&mut b, &mv);
+        assert_eq!(winner(&b), Some((PieceColor::White, Victory::MidlineInvasion)));
+    }
+
+    #[test]
+    fn no_invasion_while_in_check() {
+        let mut b = board("k3r3/8/8/4K3/8/8/8/8 b");
+        b.king_crossed = true;
+        assert_eq!(winner(&b), None);
+    }
+
+    #[test]
+    fn a_king_set_up_across_the_midline_hasnt_invaded() {
+        let b = board("k7/8/8/4K3/8/8/8/8 b");
+        assert_eq!(winner(&b), None);
+    }
+
+    #[test]
+    fn checkmate_wins() {
+        let b = board("4R2k/6pp/8/8/8/8/8/K7 b");
+        assert_eq!(winner(&b), Some((PieceColor::White, Victory::Checkmate)));
+    }
+}
@@ -0,0 +1,126 @@
+use super::*;
+use super::classic::Classic;
+
+// The Empowered army: its rooks, bishops and knights lend each other their moves when they stand side by side,
+// and the Empowered Queen gives up her own moves to walk like a king.
+pub struct Empowered;
+
+fn is_empowering(piece: BoardPiece) -> bool {
+    piece.army == ArmyStates::Empowered
+        && matches!(piece.kind, PieceCursor::Rook | PieceCursor::Bishop | PieceCursor::Knight)
+}
+
+// A rook, bishop or knight moves as itself, plus as every friendly Empowered rook, bishop or knight
+// directly above, below or beside it on the board as it stands right now.
+fn empowered_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    let mut kinds = vec![piece.kind];
+    for (dx, dy) in ORTHOGONAL {
+        if let Some(neighbour) = board.get(from.offset(dx, dy)) {
+            if neighbour.color == piece.color && is_empowering(neighbour) && !kinds.contains(&neighbour.kind) {
+                kinds.push(neighbour.kind);
+            }
+        }
+    }
+
+    for kind in kinds {
+        match kind {
+            PieceCursor::Rook => slide(board, from, piece, &ORTHOGONAL, 7, moves),
+            PieceCursor::Bishop => slide(board, from, piece, &DIAGONAL, 7, moves),
+            PieceCursor::Knight => slide(board, from, piece, &KNIGHT_JUMPS, 1, moves),
+            _ => {}
+        }
+    }
+}
+
+impl ArmyRules for Empowered {
+    fn piece_moves(&self, board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+        match piece.kind {
+            PieceCursor::Queen => slide(board, from, piece, &ALL_DIRECTIONS, 1, moves),
+            PieceCursor::Rook | PieceCursor::Bishop | PieceCursor::Knight => empowered_moves(board, from, piece, moves),
+            _ => Classic.piece_moves(board, from, piece, moves),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen2;
+
+    fn destinations(fen: &str, from: &str) -> Vec<String> {
+        let board = from_fen2(fen).unwrap();
+        let mut squares: Vec<String> = legal_destinations(&board, Position::from_algebraic(from).unwrap())
+            .iter()
+            .map(|pos| pos.to_algebraic())
+            .collect();
+        squares.sort();
+        squares
+    }
+
+    fn count(fen: &str, from: &str) -> usize {
+        destinations(fen, from).len()
+    }
+
+    // On their own, the pieces on d4 move as usual.
+    #[test]
+    fn alone_they_move_as_themselves() {
+        assert_eq!(count("k7/8/8/8/3R4/8/8/7K w EC", "d4"), 14);
+        assert_eq!(count("k7/8/8/8/3B4/8/8/7K w EC", "d4"), 13);
+        assert_eq!(count("k7/8/8/8/3N4/8/8/7K w EC", "d4"), 8);
+    }
+
+    #[test]
+    fn rook_and_bishop_share_moves() {
+        // Rook d4 gains the bishop's diagonals, and the bishop on e4 gains the rook's lines.
+        let squares = destinations("k7/8/8/8/3RB3/8/8/7K w EC", "d4");
+        assert!(squares.contains(&"a7".to_owned()) && squares.contains(&"g1".to_owned()));
+        assert_eq!(squares.len(), 10 + 13);
+        let squares = destinations("k7/8/8/8/3RB3/8/8/7K w EC", "e4");
+        assert!(squares.contains(&"e8".to_owned()) && squares.contains(&"h4".to_owned()));
+    }
+
+    #[test]
+    fn rook_and_knight_share_moves() {
+        let squares = destinations("k7/8/8/8/3R4/3N4/8/7K w EC", "d4");
+        assert!(squares.contains(&"f5".to_owned()) && squares.contains(&"b3".to_owned()));
+        let squares = destinations("k7/8/8/8/3R4/3N4/8/7K w EC", "d3");
+        assert!(squares.contains(&"h3".to_owned()) && squares.contains(&"d1".to_owned()));
+    }
+
+    #[test]
+    fn bishop_and_knight_share_moves() {
+        let squares = destinations("k7/8/8/8/2NB4/8/8/7K w EC", "d4");
+        assert!(squares.contains(&"e6".to_owned()));
+        let squares = destinations("k7/8/8/8/2NB4/8/8/7K w EC", "c4");
+        assert!(squares.contains(&"f7".to_owned()) && squares.contains(&"a2".to_owned()));
+    }
+
+    #[test]
+    fn all_three_together() {
+        // The rook on d4 has the bishop on one side and the knight on the other.
+        let squares = destinations("k7/8/8/8/2NRB3/8/8/7K w EC", "d4");
+        assert!(squares.contains(&"a7".to_owned()));
+        assert!(squares.contains(&"f5".to_owned()));
+        assert!(squares.contains(&"d8".to_owned()));
+        // The knight and bishop only touch the rook, so they don't get each other's moves.
+        let squares = destinations("k7/8/8/8/2NRB3/8/8/7K w EC", "e4");
+        assert!(!squares.contains(&"f6".to_owned()));
+        assert!(squares.contains(&"e8".to_owned()));
+    }
+
+    #[test]
+    fn diagonal_neighbours_and_enemies_lend_nothing() {
+        assert_eq!(count("k7/8/8/4B3/3R4/8/8/7K w EC", "d4"), 14);
+        assert_eq!(count("k7/8/8/8/3Rb3/8/8/K7 w EE", "d4"), 11);
+    }
+
+    #[test]
+    fn pieces_from_other_armies_lend_nothing() {
+        assert_eq!(count("k7/8/8/8/3RB(C)3/8/8/7K w EC", "d4"), 10);
+    }
+
+    #[test]
+    fn empowered_queen_moves_like_a_king() {
+        assert_eq!(destinations("k7/8/8/8/3Q4/8/8/7K w EC", "d4"), ["c3", "c4", "c5", "d3", "d5", "e3", "e4", "e5"]);
+    }
+}
@@ -0,0 +1,406 @@
+use std::fmt;
+use super::board_state::*;
+
+mod animals;
+mod classic;
+mod empowered;
+mod nemesis;
+mod reaper;
+mod twokings;
+
+use animals::Animals;
+use classic::Classic;
+use empowered::Empowered;
+use nemesis::Nemesis;
+use reaper::Reaper;
+use twokings::TwoKings;
+
+// Directions pieces move in, as (file, rank) steps.
+pub const ORTHOGONAL: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+pub const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+pub const ALL_DIRECTIONS: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+pub const KNIGHT_JUMPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    Quiet,
+    Capture,
+    DoubleStep,
+    EnPassant,
+    Castle(CastleSide),
+    // Appearing on a far-away square without crossing the board in between, like the Reaper army's pieces do.
+    // Whether anything is taken is down to `captures`, same as for every other kind of move.
+    Teleport,
+    // A Two Kings king spending its turn standing still and taking everything next to it. `from` and `to` are the same square.
+    Whirlwind,
+    // The Animals army's Tiger taking the piece on `to` and springing back to `from`.
+    Pounce,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub from: Position,
+    pub to: Position,
+    pub kind: MoveKind,
+    // The squares of the pieces being taken. Usually just `to`, but en passant takes the pawn beside it,
+    // and a whirlwind takes everything around the king.
+    pub captures: Vec<Position>,
+    pub promotion: Option<PieceCursor>,
+}
+
+impl Move {
+    pub fn new(from: Position, to: Position, kind: MoveKind) -> Self {
+        Self {
+            from,
+            to,
+            kind,
+            captures: Vec::new(),
+            promotion: None,
+        }
+    }
+
+    pub fn new_capture(from: Position, to: Position) -> Self {
+        Self {
+            captures: vec![to],
+            ..Self::new(from, to, MoveKind::Capture)
+        }
+    }
+}
+
+/// How the pieces of one army move. Each army gets its own rule set, picked by army_rules().
+pub trait ArmyRules: Sync {
+    /// Every move the piece on `from` could make, before checking whether it leaves its own king in check.
+    /// Castling isn't included - it works the same for every army, and legal_moves() adds it.
+    fn piece_moves(&self, board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>);
+
+    /// What this army's pawns may promote to.
+    /// In Chess 2 a pawn can only become a piece of its own army, and never a king.
+    fn promotions(&self) -> &'static [PieceCursor] {
+        &[PieceCursor::Queen, PieceCursor::Rook, PieceCursor::Bishop, PieceCursor::Knight]
+    }
+
+    /// Whether this army's rooks can castle.
+    fn can_castle(&self) -> bool {
+        true
+    }
+}
+
+pub fn army_rules(army: ArmyStates) -> &'static dyn ArmyRules {
+    match army {
+        ArmyStates::Nemesis => &Nemesis,
+        ArmyStates::Empowered => &Empowered,
+        ArmyStates::Reaper => &Reaper,
+        ArmyStates::TwoKings => &TwoKings,
+        ArmyStates::Animals => &Animals,
+        ArmyStates::Classic => &Classic,
+    }
+}
+
+// The Reaper army's Ghost stands in the rook's spot, and nothing can ever take it.
+pub fn is_ghost(piece: BoardPiece) -> bool {
+    piece.army == ArmyStates::Reaper && piece.kind == PieceCursor::Rook
+}
+
+pub fn can_capture(attacker: BoardPiece, target: BoardPiece) -> bool {
+    attacker.color != target.color && !is_ghost(target)
+}
+
+// Walks from `from` in each direction for up to `range` squares, stopping at the first piece in the way,
+// which it can take if it's an enemy. Leaping pieces are just sliders with a range of 1.
+pub fn slide(board: &BoardState, from: Position, piece: BoardPiece, directions: &[(i32, i32)], range: i32, moves: &mut Vec<Move>) {
+    for (dx, dy) in directions {
+        let mut to = from;
+        for _ in 0..range {
+            to = to.offset(*dx, *dy);
+            if !to.in_bounds() {
+                break;
+            }
+            match board.get(to) {
+                None => moves.push(Move::new(from, to, MoveKind::Quiet)),
+                Some(target) => {
+                    if can_capture(piece, target) {
+                        moves.push(Move::new_capture(from, to));
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// A pawn reaching the far rank has to promote, so it gets one move per piece it could become.
+fn push_pawn_move(moves: &mut Vec<Move>, mv: Move, piece: BoardPiece) {
+    if mv.to.y == piece.color.opposite().home_rank() {
+        for promotion in army_rules(piece.army).promotions() {
+            moves.push(Move {
+                promotion: Some(*promotion),
+                ..mv.clone()
+            });
+        }
+    } else {
+        moves.push(mv);
+    }
+}
+
+// Pawns move the same in every army: one step forward, two from their starting rank, and capture diagonally or en passant.
+pub fn pawn_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    let forward = piece.color.forward();
+
+    let one = from.offset(0, forward);
+    if one.in_bounds() && board.get(one).is_none() {
+        push_pawn_move(moves, Move::new(from, one, MoveKind::Quiet), piece);
+
+        let two = from.offset(0, 2 * forward);
+        if from.y == piece.color.home_rank() + forward && board.get(two).is_none() {
+            moves.push(Move::new(from, two, MoveKind::DoubleStep));
+        }
+    }
+
+    for dx in [-1, 1] {
+        let to = from.offset(dx, forward);
+        match board.get(to) {
+            Some(target) if can_capture(piece, target) => push_pawn_move(moves, Move::new_capture(from, to), piece),
+            None if to.in_bounds() && board.en_passant == Some(to) => {
+                let taken = Position::new(to.x, from.y);
+                if board.get(taken).is_some_and(|target| target.kind == PieceCursor::Pawn && can_capture(piece, target)) {
+                    moves.push(Move {
+                        captures: vec![taken],
+                        ..Move::new(from, to, MoveKind::EnPassant)
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn pseudo_moves(board: &BoardState, from: Position, piece: BoardPiece) -> Vec<Move> {
+    let mut moves = Vec::new();
+    army_rules(piece.army).piece_moves(board, from, piece, &mut moves);
+    moves
+}
+
+/// Whether any piece of `by` could take whatever is standing on `square`.
+pub fn is_attacked(board: &BoardState, square: Position, by: PieceColor) -> bool {
+    board.pieces()
+        .filter(|(_, piece)| piece.color == by)
+        .any(|(pos, piece)| pseudo_moves(board, pos, piece).iter().any(|mv| mv.captures.contains(&square)))
+}
+
+// A side is only in check once every one of its kings is attacked - the Two Kings army can leave one of its kings hanging,
+// as long as the other one is safe.
+pub fn in_check(board: &BoardState, color: PieceColor) -> bool {
+    let kings = board.kings(color);
+    !kings.is_empty() && kings.iter().all(|king| is_attacked(board, *king, color.opposite()))
+}
+
+// Castling works as in Fischer random chess, which covers the regular setup too:
+// the king ends up on the g- or c-file and the rook next to it, every square either of them crosses has to be empty,
+// and the king can't castle out of, through, or into check.
+fn castling_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    let rank = piece.color.home_rank();
+    if from.y != rank || is_attacked(board, from, piece.color.opposite()) {
+        return;
+    }
+
+    for side in [CastleSide::Short, CastleSide::Long] {
+        let rook_file = match board.castling_rook(piece.color, side) {
+            Some(file) => file,
+            None => continue,
+        };
+        let rook_from = Position::new(rook_file, rank);
+        match board.get(rook_from) {
+            Some(rook) if rook.kind == PieceCursor::Rook && rook.color == piece.color && army_rules(rook.army).can_castle() => {}
+            _ => continue,
+        }
+
+        let (king_to, rook_to) = match side {
+            CastleSide::Short => (6, 5),
+            CastleSide::Long => (2, 3),
+        };
+        let span = |a: i32, b: i32| a.min(b)..=a.max(b);
+
+        let blocked = span(from.x, king_to)
+            .chain(span(rook_file, rook_to))
+            .filter(|x| *x != from.x && *x != rook_file)
+            .any(|x| board.get(Position::new(x, rank)).is_some());
+        if blocked {
+            continue;
+        }
+
+        let through_check = span(from.x, king_to).any(|x| {
+            let square = Position::new(x, rank);
+            let mut test = board.clone();
+            test.remove(from);
+            test.remove(rook_from);
+            test.set(square, piece);
+            is_attacked(&test, square, piece.color.opposite())
+        });
+        if through_check {
+            continue;
+        }
+
+        moves.push(Move::new(from, Position::new(king_to, rank), MoveKind::Castle(side)));
+    }
+}
+
+/// Every legal move for the piece on `from`, whichever side is to move.
+pub fn legal_moves(board: &BoardState, from: Position) -> Vec<Move> {
+    let piece = match board.get(from) {
+        Some(piece) => piece,
+        None => return Vec::new(),
+    };
+
+    let mut moves = pseudo_moves(board, from, piece);
+    if piece.kind == PieceCursor::King {
+        castling_moves(board, from, piece, &mut moves);
+    }
+
+    moves.retain(|mv| {
+        let mut after = board.clone();
+        apply_move(&mut after, mv);
+        !in_check(&after, piece.color)
+    });
+    moves
+}
+
+/// The squares the piece on `from` can legally move to.
+pub fn legal_destinations(board: &BoardState, from: Position) -> Vec<Position> {
+    let mut destinations = Vec::new();
+    for mv in legal_moves(board, from) {
+        if !destinations.contains(&mv.to) {
+            destinations.push(mv.to);
+        }
+    }
+    destinations
+}
+
+/// The legal moves that picking up the piece on `from` and dropping it on `to` stands for. Whirlwinds don't go anywhere, so they're left out.
+/// Castling is dropping the king on its own rook, or on the square it castles to - unless it could step there anyway,
+/// or is already standing there, as can happen in Fischer random. Then that square means the step, and only the rook means castling.
+/// More than one move left means a pawn promoting, once for each piece it could become.
+pub fn drop_moves(board: &BoardState, from: Position, to: Position) -> Vec<Move> {
+    let color = match board.get(from) {
+        Some(piece) => piece.color,
+        None => return Vec::new(),
+    };
+    let moves: Vec<Move> = legal_moves(board, from).into_iter().filter(|mv| mv.kind != MoveKind::Whirlwind).collect();
+    let steps_there = moves.iter().any(|mv| mv.to == to && !matches!(mv.kind, MoveKind::Castle(_)));
+    moves.iter()
+        .filter(|mv| match mv.kind {
+            MoveKind::Castle(side) => {
+                let rook = board.castling_rook(color, side).map(|file| Position::new(file, from.y));
+                rook == Some(to) || (mv.to == to && to != from && !steps_there)
+            }
+            _ => mv.to == to,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Plays a move on the board, handing back the pieces it captured.
+/// Also takes care of the rook when castling, castling rights, the en passant square and the side to move.
+pub fn apply_move(board: &mut BoardState, mv: &Move) -> Vec<BoardPiece> {
+    let mut piece = match board.get(mv.from) {
+        Some(piece) => piece,
+        None => return Vec::new(),
+    };
+    let color = piece.color;
+    let captured: Vec<BoardPiece> = mv.captures.iter().filter_map(|square| board.remove(*square)).collect();
+
+    // Only the captures happen for a pouncing Tiger - it stays on its own square.
+    if let MoveKind::Castle(side) = mv.kind {
+        let rank = color.home_rank();
+        let rook_from = board.castling_rook(color, side).map(|file| Position::new(file, rank));
+        let rook = rook_from.and_then(|square| board.remove(square));
+        board.remove(mv.from);
+        board.set(mv.to, piece);
+        if let Some(rook) = rook {
+            let rook_to = match side {
+                CastleSide::Short => 5,
+                CastleSide::Long => 3,
+            };
+            board.set(Position::new(rook_to, rank), rook);
+        }
+    } else if mv.kind != MoveKind::Pounce {
+        if let Some(promotion) = mv.promotion {
+            piece.kind = promotion;
+        }
+        board.remove(mv.from);
+        board.set(mv.to, piece);
+    }
+
+    // A king that moves can't castle any more, and neither can a rook that moves or gets taken.
+    for side_color in [PieceColor::White, PieceColor::Black] {
+        for side in [CastleSide::Short, CastleSide::Long] {
+            let rook = board.castling_rook(side_color, side).map(|file| Position::new(file, side_color.home_rank()));
+            let king_moved = side_color == color && piece.kind == PieceCursor::King;
+            if king_moved || rook == Some(mv.from) || rook.is_some_and(|rook| mv.captures.contains(&rook)) {
+                board.set_castling_rook(side_color, side, None);
+            }
+        }
+    }
+
+    board.en_passant = match mv.kind {
+        MoveKind::DoubleStep => Some(mv.from.offset(0, color.forward())),
+        _ => None,
+    };
+    board.side_to_move = color.opposite();
+    board.king_crossed = piece.is_royal() && board.get(mv.to) == Some(piece)
+        && !across_midline(color, mv.from) && across_midline(color, mv.to);
+    board.king_taken = captured.iter().any(|taken| taken.is_royal() && board.kings(taken.color).is_empty());
+    captured
+}
+
+// The ways a game of Chess 2 can be won.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Victory {
+    Checkmate,
+    // Every one of the loser's kings has been taken. Only the Two Kings army can leave a king where it can be taken.
+    KingsCaptured,
+    // A king made it across the midline into the opponent's half, and isn't in check there.
+    // With two kings, both of them have to make it across.
+    MidlineInvasion,
+}
+
+impl fmt::Display for Victory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Victory::Checkmate => write!(f, "checkmate"),
+            Victory::KingsCaptured => write!(f, "capturing the kings"),
+            Victory::MidlineInvasion => write!(f, "midline invasion"),
+        }
+    }
+}
+
+// The midline runs between the 4th and 5th ranks, so White invades on ranks 5 to 8 and Black on ranks 1 to 4.
+fn across_midline(color: PieceColor, square: Position) -> bool {
+    match color {
+        PieceColor::White => square.y >= 4,
+        PieceColor::Black => square.y <= 3,
+    }
+}
+
+fn has_invaded(board: &BoardState, color: PieceColor) -> bool {
+    let kings = board.kings(color);
+    !kings.is_empty() && kings.iter().all(|king| across_midline(color, *king) && !is_attacked(board, *king, color.opposite()))
+}
+
+/// Who has won the game on the board, and how - or None if it's still going.
+/// Kings only count as taken, or as having invaded, if the last move did it - not if the board was set up that way.
+pub fn winner(board: &BoardState) -> Option<(PieceColor, Victory)> {
+    let side = board.side_to_move;
+    if board.king_taken && board.kings(side).is_empty() {
+        return Some((side.opposite(), Victory::KingsCaptured));
+    }
+
+    // Only the side that just moved can have invaded - a king that crossed into check has to get out of it first.
+    if board.king_crossed && has_invaded(board, side.opposite()) {
+        return Some((side.opposite(), Victory::MidlineInvasion));
+    }
+
+    let stuck = board.pieces()
+        .filter(|(_, piece)| piece.color == side)
+        .all(|(pos, _)| legal_moves(board, pos).is_empty());
+    (stuck && in_check(board, side)).then_some((side.opposite(), Victory::Checkmate))
+}
@@ -0,0 +1,94 @@
+use super::*;
+use super::classic::Classic;
+
+// The Nemesis army: the Nemesis replaces the queen, and its pawns are drawn towards the enemy king.
+pub struct Nemesis;
+
+// The Nemesis moves like a queen, but the only piece it can take is the enemy king (or Warrior King).
+fn nemesis_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    for (dx, dy) in ALL_DIRECTIONS {
+        let mut to = from.offset(dx, dy);
+        while to.in_bounds() {
+            match board.get(to) {
+                None => moves.push(Move::new(from, to, MoveKind::Quiet)),
+                Some(target) => {
+                    if target.is_royal() && can_capture(piece, target) {
+                        moves.push(Move::new_capture(from, to));
+                    }
+                    break;
+                }
+            }
+            to = to.offset(dx, dy);
+        }
+    }
+}
+
+// On top of their usual moves, Nemesis pawns may step onto any empty square next to them that brings them closer to an enemy king -
+// closer on the file and rank alike, so they never step away from it in either direction. They can't capture this way.
+fn nemesis_pawn_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    pawn_moves(board, from, piece, moves);
+
+    for king in board.kings(piece.color.opposite()) {
+        for (dx, dy) in ALL_DIRECTIONS {
+            let to = from.offset(dx, dy);
+            let (old_dx, old_dy) = ((king.x - from.x).abs(), (king.y - from.y).abs());
+            let (new_dx, new_dy) = ((king.x - to.x).abs(), (king.y - to.y).abs());
+            let closer = new_dx <= old_dx && new_dy <= old_dy && (new_dx, new_dy) != (old_dx, old_dy);
+            let already_listed = moves.iter().any(|mv| mv.from == from && mv.to == to);
+            if to.in_bounds() && closer && board.get(to).is_none() && !already_listed {
+                push_pawn_move(moves, Move::new(from, to, MoveKind::Quiet), piece);
+            }
+        }
+    }
+}
+
+impl ArmyRules for Nemesis {
+    fn piece_moves(&self, board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+        match piece.kind {
+            PieceCursor::Queen => nemesis_moves(board, from, piece, moves),
+            PieceCursor::Pawn => nemesis_pawn_moves(board, from, piece, moves),
+            _ => Classic.piece_moves(board, from, piece, moves),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen2;
+
+    fn destinations(fen: &str, from: &str) -> Vec<String> {
+        let board = from_fen2(fen).unwrap();
+        let mut squares: Vec<String> = legal_destinations(&board, Position::from_algebraic(from).unwrap())
+            .iter()
+            .map(|pos| pos.to_algebraic())
+            .collect();
+        squares.sort();
+        squares
+    }
+
+    #[test]
+    fn nemesis_only_captures_the_king() {
+        // The Nemesis on d1 can't take the knight on d4, but it can take the king on h5.
+        let squares = destinations("8/8/8/7k/3n4/8/8/K2Q4 w NC", "d1");
+        assert!(squares.contains(&"d3".to_owned()));
+        assert!(!squares.contains(&"d4".to_owned()));
+        assert!(squares.contains(&"h5".to_owned()));
+    }
+
+    #[test]
+    fn nemesis_pawns_step_towards_the_enemy_king() {
+        // Forward and double step as usual, plus the three empty squares that close in on the king on a8 without backing off.
+        assert_eq!(destinations("k7/8/8/8/8/8/4P3/7K w NC", "e2"), ["d2", "d3", "e3", "e4"]);
+    }
+
+    #[test]
+    fn nemesis_pawns_cant_capture_towards_the_king() {
+        assert_eq!(destinations("k7/8/8/8/8/8/3nP3/7K w NC", "e2"), ["d3", "e3", "e4"]);
+    }
+
+    #[test]
+    fn classic_pieces_are_unaffected() {
+        assert_eq!(destinations("k7/8/8/8/8/8/8/R6K w NC", "a1").len(), 13);
+    }
+}
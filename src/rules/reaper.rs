@@ -0,0 +1,106 @@
+use super::*;
+use super::classic::Classic;
+
+// The Reaper army: the Ghost replaces the rooks and the Reaper replaces the queen, and neither of them walks anywhere.
+pub struct Reaper;
+
+// The Ghost can teleport to any empty square on the board. It can't take anything, and nothing can take it.
+fn ghost_moves(board: &BoardState, from: Position, moves: &mut Vec<Move>) {
+    for x in 0..8 {
+        for y in 0..8 {
+            let to = Position::new(x, y);
+            if board.get(to).is_none() {
+                moves.push(Move::new(from, to, MoveKind::Teleport));
+            }
+        }
+    }
+}
+
+// The Reaper can teleport to any square except the opponent's back rank, taking whatever is there - as long as it isn't a king.
+fn reaper_moves(board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+    let forbidden_rank = piece.color.opposite().home_rank();
+    for x in 0..8 {
+        for y in 0..8 {
+            let to = Position::new(x, y);
+            if to == from || y == forbidden_rank {
+                continue;
+            }
+            match board.get(to) {
+                None => moves.push(Move::new(from, to, MoveKind::Teleport)),
+                Some(target) if !target.is_royal() && can_capture(piece, target) => moves.push(Move {
+                    captures: vec![to],
+                    ..Move::new(from, to, MoveKind::Teleport)
+                }),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl ArmyRules for Reaper {
+    fn piece_moves(&self, board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+        match piece.kind {
+            PieceCursor::Rook => ghost_moves(board, from, moves),
+            PieceCursor::Queen => reaper_moves(board, from, piece, moves),
+            _ => Classic.piece_moves(board, from, piece, moves),
+        }
+    }
+
+    // There's no rook to castle with - just the Ghost.
+    fn can_castle(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen2;
+
+    fn moves(fen: &str, from: &str) -> Vec<Move> {
+        legal_moves(&from_fen2(fen).unwrap(), Position::from_algebraic(from).unwrap())
+    }
+
+    fn reaches(moves: &[Move], square: &str) -> bool {
+        moves.iter().any(|mv| mv.to == Position::from_algebraic(square).unwrap())
+    }
+
+    #[test]
+    fn ghost_teleports_to_every_empty_square() {
+        let ghost = moves("k7/8/8/8/3n4/8/8/R6K w RC", "a1");
+        // 64 squares, minus the ghost itself and three occupied ones.
+        assert_eq!(ghost.len(), 60);
+        assert!(ghost.iter().all(|mv| mv.kind == MoveKind::Teleport && mv.captures.is_empty()));
+        assert!(!reaches(&ghost, "d4"));
+    }
+
+    #[test]
+    fn ghost_cant_be_captured() {
+        // The black rook on d8 would take anything else on d1.
+        let rook = moves("k2r4/8/8/8/8/8/8/3R3K b RC", "d8");
+        assert!(!reaches(&rook, "d1"));
+        assert!(reaches(&rook, "d2"));
+    }
+
+    #[test]
+    fn reaper_teleports_anywhere_but_the_far_rank() {
+        let reaper = moves("k7/8/8/8/3n4/8/8/Q6K w RC", "a1");
+        assert!(reaches(&reaper, "h7"));
+        assert!(!reaches(&reaper, "b8"));
+        assert!(reaper.iter().any(|mv| mv.captures == [Position::from_algebraic("d4").unwrap()]));
+    }
+
+    #[test]
+    fn reaper_cant_capture_kings_or_ghosts() {
+        let reaper = moves("8/8/8/8/3k4/8/6r(R)1/Q6K w RC", "a1");
+        assert!(!reaches(&reaper, "d4"));
+        assert!(!reaches(&reaper, "g2"));
+    }
+
+    #[test]
+    fn reaper_army_cant_castle() {
+        let king = moves("4k3/8/8/8/8/8/8/R3K2R w RC", "e1");
+        assert!(!reaches(&king, "g1"));
+        assert!(!reaches(&king, "c1"));
+    }
+}
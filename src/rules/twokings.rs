@@ -0,0 +1,126 @@
+use super::*;
+use super::classic::Classic;
+
+// The Two Kings army: the Warrior King replaces the queen, so the army has two kings to look after,
+// and either king can spend its turn on a whirlwind.
+pub struct TwoKings;
+
+// Instead of moving, a king can stay where it is and take every piece on the squares around it - its own included.
+// Ghosts are the only thing that survives. A whirlwind with nothing to hit isn't a move.
+fn whirlwind(board: &BoardState, from: Position, moves: &mut Vec<Move>) {
+    let captures: Vec<Position> = ALL_DIRECTIONS
+        .iter()
+        .map(|(dx, dy)| from.offset(*dx, *dy))
+        .filter(|square| board.get(*square).is_some_and(|target| !is_ghost(target)))
+        .collect();
+    if !captures.is_empty() {
+        moves.push(Move {
+            captures,
+            ..Move::new(from, from, MoveKind::Whirlwind)
+        });
+    }
+}
+
+impl ArmyRules for TwoKings {
+    fn piece_moves(&self, board: &BoardState, from: Position, piece: BoardPiece, moves: &mut Vec<Move>) {
+        match piece.kind {
+            // The Warrior King walks like a king, and is just as royal.
+            PieceCursor::King | PieceCursor::Queen => {
+                slide(board, from, piece, &ALL_DIRECTIONS, 1, moves);
+                whirlwind(board, from, moves);
+            }
+            _ => Classic.piece_moves(board, from, piece, moves),
+        }
+    }
+
+    // Without a queen, and a pawn can't become a Warrior King either.
+    fn promotions(&self) -> &'static [PieceCursor] {
+        &[PieceCursor::Rook, PieceCursor::Bishop, PieceCursor::Knight]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen2;
+
+    fn square(name: &str) -> Position {
+        Position::from_algebraic(name).unwrap()
+    }
+
+    fn find_move(board: &BoardState, from: &str, kind: MoveKind) -> Option<Move> {
+        legal_moves(board, square(from)).into_iter().find(|mv| mv.kind == kind)
+    }
+
+    #[test]
+    fn warrior_king_moves_as_a_king() {
+        let b = from_fen2("k7/8/8/8/3Q4/8/8/7K w TC").unwrap();
+        let moves = legal_moves(&b, square("d4"));
+        assert_eq!(moves.len(), 8);
+        // There's nothing next to it, so no whirlwind either.
+        assert!(moves.iter().all(|mv| mv.kind == MoveKind::Quiet));
+    }
+
+    #[test]
+    fn whirlwind_takes_friend_and_foe_but_not_ghosts() {
+        // The black rook on e5 is a Ghost.
+        let mut b = from_fen2("k7/8/8/2p1r3/3K4/2P5/8/8 w TR").unwrap();
+        let mv = find_move(&b, "d4", MoveKind::Whirlwind).unwrap();
+        assert_eq!(mv.from, mv.to);
+        assert_eq!(apply_move(&mut b, &mv).len(), 2);
+        assert!(b.get(square("c5")).is_none());
+        assert!(b.get(square("c3")).is_none());
+        assert!(b.get(square("e5")).is_some());
+        assert_eq!(b.get(square("d4")).unwrap().kind, PieceCursor::King);
+        assert_eq!(b.side_to_move, PieceColor::Black);
+    }
+
+    #[test]
+    fn one_king_can_be_left_hanging() {
+        // The Warrior King on h1 is attacked, but the king on a1 is safe, so White isn't in check.
+        let mut b = from_fen2("k6r/8/8/8/8/8/8/K6Q w TC").unwrap();
+        assert!(!in_check(&b, PieceColor::White));
+        assert!(legal_moves(&b, square("a1")).iter().any(|mv| mv.to == square("a2")));
+
+        let mv = legal_moves(&b, square("h8")).into_iter().find(|mv| mv.to == square("h1")).unwrap();
+        apply_move(&mut b, &mv);
+        assert_eq!(b.kings(PieceColor::White), vec![square("a1")]);
+        assert!(in_check(&b, PieceColor::White));
+        assert_eq!(winner(&b), None);
+    }
+
+    #[test]
+    fn taking_the_last_king_wins() {
+        let mut b = from_fen2("8/8/8/3k4/3K4/8/8/8 w TT").unwrap();
+        let mv = find_move(&b, "d4", MoveKind::Whirlwind).unwrap();
+        apply_move(&mut b, &mv);
+        assert_eq!(winner(&b), Some((PieceColor::White, Victory::KingsCaptured)));
+    }
+
+    #[test]
+    fn both_kings_have_to_invade() {
+        // The king crosses to e5, but the Warrior King on a3 is still on White's side.
+        let mut b = from_fen2("k7/8/8/8/4K3/Q7/8/8 w TC").unwrap();
+        let mv = legal_moves(&b, square("e4")).into_iter().find(|mv| mv.to == square("e5")).unwrap();
+        apply_move(&mut b, &mv);
+        assert_eq!(winner(&b), None);
+
+        let mut b = from_fen2("k7/8/8/4K3/Q7/8/8/8 w TC").unwrap();
+        let mv = legal_moves(&b, square("a4")).into_iter().find(|mv| mv.to == square("a5")).unwrap();
+        apply_move(&mut b, &mv);
+        assert_eq!(winner(&b), Some((PieceColor::White, Victory::MidlineInvasion)));
+    }
+
+    #[test]
+    fn a_board_set_up_without_kings_hasnt_been_won() {
+        let b = from_fen2("8/8/8/8/3K4/8/8/8 w TT").unwrap();
+        assert_eq!(winner(&b), None);
+    }
+
+    #[test]
+    fn pawns_cant_promote_to_a_warrior_king() {
+        let b = from_fen2("k7/4P3/8/8/8/8/8/K7 w TC").unwrap();
+        let promotions: Vec<PieceCursor> = legal_moves(&b, square("e7")).iter().filter_map(|mv| mv.promotion).collect();
+        assert_eq!(promotions, vec![PieceCursor::Rook, PieceCursor::Bishop, PieceCursor::Knight]);
+    }
+}
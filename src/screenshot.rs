@@ -1,25 +1,277 @@
 use super::*;
-use win_screenshot::addon::*;
-use win_screenshot::capture::*;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use bevy::core_pipeline::{draw_2d_graph, node, Transparent2d};
+use bevy::render::camera::{ActiveCamera, CameraTypePlugin, ExtractedCamera, RenderTarget};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotValue};
+use bevy::render::render_phase::RenderPhase;
+use bevy::render::render_resource::{
+    Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout, MapMode, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::ViewTarget;
+use bevy::render::{RenderApp, RenderStage};
 use chrono::prelude::{Local};
+use image::RgbaImage;
 
-#[cfg(target_os = "windows")]
-pub fn take_screenshot(
+// The board is rendered a second time by a camera of its own, into an image instead of the window,
+// and that frame is copied back off the GPU and saved - pieces, circles, arrows, notation, stone counters and all.
+// The move list and any prompts are drawn by egui straight onto the window, so they're left out.
+// Ctrl + S saves it at the size it's shown in the window, Ctrl + Shift + S at twice that.
+// Asking for one while a piece is being dragged waits until it's put down, so the highlights aren't in it.
+
+// The render graph nodes that draw the screenshot camera's view, and then copy it out.
+const SCREENSHOT_DRIVER: &str = "screenshot_driver";
+const SCREENSHOT_COPY: &str = "screenshot_copy";
+
+#[derive(Component, Default)]
+pub struct ScreenshotCamera;
+
+// How many times bigger than the board on screen the next screenshot should be, once one's been asked for.
+#[derive(Default)]
+struct ScreenshotRequest(Option<u32>);
+
+// Where the render world leaves the frame it read back, for the app to save. Both worlds hold on to the same one.
+#[derive(Clone, Default)]
+struct CapturedFrame(Arc<Mutex<Option<RgbaImage>>>);
+
+// A frame copied out of the screenshot camera's image, waiting to be read once the GPU has finished with it.
+struct PendingCopy {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_row: usize,
+    format: TextureFormat,
+}
+
+// Render world only. Nodes can't change the world, hence the mutex.
+#[derive(Default)]
+struct ScreenshotCopy(Mutex<Option<PendingCopy>>);
+
+fn take_screenshot(
+    mut commands: Commands,
     kbd: Res<Input<KeyCode>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    mut request: ResMut<ScreenshotRequest>,
+    cameras: Query<(), With<ScreenshotCamera>>,
 ) {
     if (kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl)) && kbd.just_pressed(KeyCode::S) {
-        info!("Screenshot saved.");
-
-        match capture_window(find_window("Chess 2 Board Editor").unwrap()).ok() {
-            Some(image) => {
-                let timestamp = Local::now().format("%Y%m%d_%H-%M-%S").to_string();
-                let filepath = format!("chess2-canvas_{}.png", timestamp);
-                image
-                    .save(filepath)
-                    .unwrap()
-            },
-            None => error!("Could not find the window..."),
+        let scale = if kbd.pressed(KeyCode::LShift) || kbd.pressed(KeyCode::RShift) { 2 } else { 1 };
+        request.0 = Some(scale);
+    }
+    let scale = match request.0 {
+        Some(scale) if !mouse_button_input.pressed(MouseButton::Left) && cameras.is_empty() => scale,
+        _ => return,
+    };
+    request.0 = None;
+
+    let window = windows.get_primary().unwrap();
+    let side = window.physical_height() * scale;
+    let size = Extent3d {
+        width: side,
+        height: side,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("screenshot"),
+            size,
+            dimension: TextureDimension::D2,
+            // The same format the window is drawn in, so the board's pipelines can draw into it too.
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    // Centred on the board rather than the window, and zoomed so the board fills the image whatever its size.
+    let camera = OrthographicCameraBundle::new_2d();
+    commands.spawn_bundle(OrthographicCameraBundle {
+        camera: Camera {
+            target: RenderTarget::Image(image),
+            ..camera.camera
+        },
+        orthographic_projection: OrthographicProjection {
+            scale: board::board_size(window) / side as f32,
+            ..camera.orthographic_projection
+        },
+        visible_entities: camera.visible_entities,
+        frustum: camera.frustum,
+        transform: camera.transform,
+        global_transform: camera.global_transform,
+        marker: ScreenshotCamera,
+    });
+}
+
+// Once the render world has read the frame back, save it and put the camera away until next time.
+fn save_screenshot(
+    mut commands: Commands,
+    frame: Res<CapturedFrame>,
+    mut images: ResMut<Assets<Image>>,
+    cameras: Query<(Entity, &Camera), With<ScreenshotCamera>>,
+) {
+    let image = match frame.0.lock().unwrap().take() {
+        Some(image) => image,
+        None => return,
+    };
+    for (entity, camera) in cameras.iter() {
+        if let RenderTarget::Image(handle) = &camera.target {
+            images.remove(handle);
         }
+        commands.entity(entity).despawn();
+    }
+
+    let timestamp = Local::now().format("%Y%m%d_%H-%M-%S").to_string();
+    let filepath = format!("chess2-canvas_{}.png", timestamp);
+    match image.save(&filepath) {
+        Ok(()) => info!("Screenshot saved to {}.", filepath),
+        Err(e) => error!("Couldn't save the screenshot: {}", e),
+    }
+}
+
+// The core pipeline only gives the window's camera somewhere to queue sprites and shapes, so do the same for ours.
+fn extract_screenshot_phases(mut commands: Commands, active: Res<ActiveCamera<ScreenshotCamera>>) {
+    if let Some(entity) = active.get() {
+        commands.get_or_spawn(entity).insert(RenderPhase::<Transparent2d>::default());
+    }
+}
+
+// Draws the board into the screenshot camera's image, the same way the window's camera draws it on screen.
+struct ScreenshotDriver {
+    query: QueryState<Entity, (With<ScreenshotCamera>, With<ViewTarget>)>,
+}
+
+impl Node for ScreenshotDriver {
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(&self, graph: &mut RenderGraphContext, _render_context: &mut RenderContext, world: &World) -> Result<(), NodeRunError> {
+        for camera in self.query.iter_manual(world) {
+            graph.run_sub_graph(draw_2d_graph::NAME, vec![SlotValue::Entity(camera)])?;
+        }
+        Ok(())
+    }
+}
+
+// Copies the finished image into a buffer that can be read from the CPU. Rows have to be padded out to 256 bytes.
+struct ScreenshotCopyNode {
+    query: QueryState<&'static ExtractedCamera, (With<ScreenshotCamera>, With<ViewTarget>)>,
+}
+
+impl Node for ScreenshotCopyNode {
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(&self, _graph: &mut RenderGraphContext, render_context: &mut RenderContext, world: &World) -> Result<(), NodeRunError> {
+        let images = world.resource::<RenderAssets<Image>>();
+        for camera in self.query.iter_manual(world) {
+            let image = match &camera.target {
+                RenderTarget::Image(handle) => images.get(handle),
+                RenderTarget::Window(_) => None,
+            };
+            let (image, size) = match (image, camera.physical_size) {
+                (Some(image), Some(size)) => (image, size),
+                _ => continue,
+            };
+            let padded_row = RenderDevice::align_copy_bytes_per_row(size.x as usize * 4);
+            let buffer = render_context.render_device.create_buffer(&BufferDescriptor {
+                label: Some("screenshot_buffer"),
+                size: (padded_row * size.y as usize) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            render_context.command_encoder.copy_texture_to_buffer(
+                image.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(padded_row as u32),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+            *world.resource::<ScreenshotCopy>().0.lock().unwrap() = Some(PendingCopy {
+                buffer,
+                width: size.x,
+                height: size.y,
+                padded_row,
+                format: image.texture_format,
+            });
+        }
+        Ok(())
+    }
+}
+
+// After the frame's been submitted, wait for the copy to land and hand the pixels over to the app.
+fn read_back_screenshot(render_device: Res<RenderDevice>, copy: Res<ScreenshotCopy>, frame: Res<CapturedFrame>) {
+    let pending = match copy.0.lock().unwrap().take() {
+        Some(pending) => pending,
+        None => return,
+    };
+    let slice = pending.buffer.slice(..);
+    render_device.map_buffer(&slice, MapMode::Read);
+    let mut pixels = Vec::with_capacity((pending.width * pending.height * 4) as usize);
+    for row in slice.get_mapped_range().chunks(pending.padded_row) {
+        pixels.extend_from_slice(&row[..pending.width as usize * 4]);
+    }
+    pending.buffer.unmap();
+    // The window's format is usually blue-green-red, but PNGs are red-green-blue.
+    if matches!(pending.format, TextureFormat::Bgra8UnormSrgb | TextureFormat::Bgra8Unorm) {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    *frame.0.lock().unwrap() = RgbaImage::from_raw(pending.width, pending.height, pixels);
+}
+
+pub struct ScreenshotPlugin;
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        let frame = CapturedFrame::default();
+        app.add_plugin(CameraTypePlugin::<ScreenshotCamera>::default())
+            .init_resource::<ScreenshotRequest>()
+            .insert_resource(frame.clone())
+            .add_system(take_screenshot)
+            .add_system(save_screenshot);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => render_app,
+            Err(_) => return,
+        };
+        render_app
+            .insert_resource(frame)
+            .init_resource::<ScreenshotCopy>()
+            .add_system_to_stage(RenderStage::Extract, extract_screenshot_phases)
+            .add_system_to_stage(RenderStage::Cleanup, read_back_screenshot);
 
+        let driver = ScreenshotDriver { query: QueryState::new(&mut render_app.world) };
+        let copy = ScreenshotCopyNode { query: QueryState::new(&mut render_app.world) };
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        graph.add_node(SCREENSHOT_DRIVER, driver);
+        graph.add_node(SCREENSHOT_COPY, copy);
+        // Like the window's own pass: after everything's been cleared, with whatever the main pass depends on ready.
+        graph.add_node_edge(node::MAIN_PASS_DEPENDENCIES, SCREENSHOT_DRIVER).unwrap();
+        graph.add_node_edge(node::CLEAR_PASS_DRIVER, SCREENSHOT_DRIVER).unwrap();
+        graph.add_node_edge(SCREENSHOT_DRIVER, SCREENSHOT_COPY).unwrap();
     }
-}
\ No newline at end of file
+}
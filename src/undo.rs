@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use super::annotations::Annotations;
+use super::board_state::BoardState;
+use super::duel::Duel;
+use super::history::GameHistory;
+
+// Undo and redo for the editor. Rather than every system saying what it did, the board and annotations are
+// compared with how they stood after the last edit whenever either changes, and the difference becomes an edit.
+// That covers drops, drawing and overwriting pieces, deleting them, army changes, resets, loading and markers alike.
+// Moves played in a game (and stepping through one) belong to the move list instead, so they aren't undoable here -
+// and nothing from before them is either, since undoing it would drop the moves without the move list or duel knowing.
+
+// Edits further back than this are forgotten.
+const MAX_EDITS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub board: BoardState,
+    pub annotations: Annotations,
+}
+
+// One edit: how things stood before it and after it.
+#[derive(Debug, Clone)]
+struct Edit {
+    before: Snapshot,
+    after: Snapshot,
+}
+
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    // How things stand after the last edit, undo or redo - None until the board has been set up.
+    current: Option<Snapshot>,
+}
+
+impl UndoStack {
+    /// Notes down how things stand now, as an edit if they've changed since last time.
+    /// A new edit means there's nothing left to redo. Hands back whether the edit changed the board, rather than just what's drawn on it.
+    pub fn record(&mut self, now: Snapshot) -> bool {
+        let before = match self.current.replace(now.clone()) {
+            Some(before) if before != now => before,
+            _ => return false,
+        };
+        let board_changed = before.board != now.board;
+        self.undo.push(Edit { before, after: now });
+        if self.undo.len() > MAX_EDITS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        board_changed
+    }
+
+    /// Goes along with a change that isn't an edit, like a move being played, so that the next edit starts from here.
+    /// Edits from before it can't be undone or redone any more: going back to them would take the move back too.
+    pub fn follow(&mut self, now: Snapshot) {
+        self.current = Some(now);
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Takes back the last edit, handing back how things should look now.
+    pub fn undo(&mut self) -> Option<Snapshot> {
+        let edit = self.undo.pop()?;
+        self.current = Some(edit.before.clone());
+        let before = edit.before.clone();
+        self.redo.push(edit);
+        Some(before)
+    }
+
+    /// Puts back the last edit that was undone.
+    pub fn redo(&mut self) -> Option<Snapshot> {
+        let edit = self.redo.pop()?;
+        self.current = Some(edit.after.clone());
+        let after = edit.after.clone();
+        self.undo.push(edit);
+        Some(after)
+    }
+}
+
+// Runs last thing every frame, once everything that could have changed the board has had its go.
+// An edit to the board - loading a position, resetting it, or changing a square in the editor - also ends any duel,
+// since the capture it was about isn't on the board any more.
+pub fn track_edits(
+    board: Res<BoardState>,
+    annotations: Res<Annotations>,
+    history: Res<GameHistory>,
+    mut undo: ResMut<UndoStack>,
+    mut duel: ResMut<Duel>,
+) {
+    if !board.is_changed() && !annotations.is_changed() {
+        return;
+    }
+    let now = Snapshot {
+        board: board.clone(),
+        annotations: annotations.clone(),
+    };
+    if history.is_changed() {
+        undo.follow(now);
+    } else if undo.record(now) && *duel != Duel::Idle {
+        *duel = Duel::Idle;
+    }
+}
+
+// Ctrl + Z to undo, Ctrl + Y (or Ctrl + Shift + Z) to redo.
+pub fn undo_redo(
+    kbd: Res<Input<KeyCode>>,
+    mut undo: ResMut<UndoStack>,
+    mut board: ResMut<BoardState>,
+    mut annotations: ResMut<Annotations>,
+    mut duel: ResMut<Duel>,
+) {
+    if !(kbd.pressed(KeyCode::LControl) || kbd.pressed(KeyCode::RControl)) {
+        return;
+    }
+    let shift = kbd.pressed(KeyCode::LShift) || kbd.pressed(KeyCode::RShift);
+
+    let snapshot = if kbd.just_pressed(KeyCode::Z) && !shift {
+        undo.undo().or_else(|| {
+            info!("Nothing to undo.");
+            None
+        })
+    } else if kbd.just_pressed(KeyCode::Y) || (kbd.just_pressed(KeyCode::Z) && shift) {
+        undo.redo().or_else(|| {
+            info!("Nothing to redo.");
+            None
+        })
+    } else {
+        return;
+    };
+
+    if let Some(snapshot) = snapshot {
+        // Only touch what the edit changed, so the other doesn't get redrawn for nothing.
+        // A duel belongs to the capture that started it, which isn't on the board any more.
+        if *board != snapshot.board {
+            *board = snapshot.board;
+            if *duel != Duel::Idle {
+                *duel = Duel::Idle;
+            }
+        }
+        if *annotations != snapshot.annotations {
+            *annotations = snapshot.annotations;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::{Brush, MarkedSquare};
+    use crate::board_state::*;
+
+    fn snapshot(board: &BoardState, markers: &[Position]) -> Snapshot {
+        let markers = markers.iter().map(|square| MarkedSquare { square: *square, brush: Brush::Green }).collect();
+        Snapshot {
+            board: board.clone(),
+            annotations: Annotations { markers, ..Annotations::default() },
+        }
+    }
+
+    #[test]
+    fn undoing_and_redoing_edits() {
+        let mut stack = UndoStack::default();
+        let mut board = BoardState::starting_position(ArmyStates::Classic, ArmyStates::Classic);
+        stack.record(snapshot(&board, &[]));
+        let start = snapshot(&board, &[]);
+
+        board.remove(Position::new(4, 1));
+        assert!(stack.record(snapshot(&board, &[])));
+        let deleted = snapshot(&board, &[]);
+        // Drawing on the board isn't a change to the board itself.
+        assert!(!stack.record(snapshot(&board, &[Position::new(0, 0)])));
+
+        assert_eq!(stack.undo().unwrap(), deleted);
+        assert_eq!(stack.undo().unwrap(), start);
+        assert_eq!(stack.undo(), None);
+        assert_eq!(stack.redo().unwrap(), deleted);
+
+        // A new edit after undoing throws away what could have been redone.
+        board.set_army(PieceColor::Black, ArmyStates::Reaper);
+        stack.record(snapshot(&board, &[]));
+        assert_eq!(stack.redo(), None);
+        assert_eq!(stack.undo().unwrap(), deleted);
+    }
+
+    #[test]
+    fn moves_in_a_game_are_not_edits() {
+        let mut stack = UndoStack::default();
+        let mut board = BoardState::starting_position(ArmyStates::Classic, ArmyStates::Classic);
+        stack.record(snapshot(&board, &[]));
+        let start = snapshot(&board, &[]);
+
+        board.move_piece(Position::new(4, 1), Position::new(4, 3));
+        stack.follow(snapshot(&board, &[]));
+        // Nothing actually changed, e.g. a piece was dropped back where it came from.
+        assert!(!stack.record(snapshot(&board, &[])));
+        assert_eq!(stack.undo(), None);
+
+        board.remove(Position::new(0, 1));
+        stack.record(snapshot(&board, &[]));
+        assert_ne!(stack.undo().unwrap(), start);
+    }
+
+    #[test]
+    fn edits_before_a_move_cant_be_undone() {
+        let mut stack = UndoStack::default();
+        let mut board = BoardState::starting_position(ArmyStates::Classic, ArmyStates::Classic);
+        stack.record(snapshot(&board, &[]));
+        board.remove(Position::new(0, 0));
+        stack.record(snapshot(&board, &[]));
+        let deleted = snapshot(&board, &[]);
+        assert_eq!(stack.undo().unwrap().board.pieces().count(), 32);
+        assert_eq!(stack.redo().unwrap(), deleted);
+
+        board.move_piece(Position::new(4, 1), Position::new(4, 3));
+        stack.follow(snapshot(&board, &[]));
+        assert_eq!(stack.undo(), None);
+        assert_eq!(stack.redo(), None);
+
+        // Edits after the move can still be undone, back as far as the move.
+        let moved = snapshot(&board, &[]);
+        stack.record(snapshot(&board, &[Position::new(4, 3)]));
+        assert_eq!(stack.undo().unwrap(), moved);
+        assert_eq!(stack.undo(), None);
+    }
+}